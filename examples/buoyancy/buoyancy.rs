@@ -3,6 +3,7 @@ use kiss3d::light::Light;
 use kiss3d::window::Window;
 use nalgebra::Point3;
 use rust_physics_engine::particle::force_generator::buoyancy::Buoyancy;
+use rust_physics_engine::particle::force_generator::drag::WATER_DENSITY;
 use rust_physics_engine::particle::force_generator::gravity::Gravity;
 use rust_physics_engine::particle::force_generator::ForceGenerator;
 use rust_physics_engine::particle::particle_trait::ParticleTrait;
@@ -10,6 +11,7 @@ use rust_physics_engine::particle::Particle;
 use rust_physics_engine::timing::TimingData;
 use rust_physics_engine::types::Real;
 use rust_physics_engine::vector::Vec3;
+use rust_physics_engine::water_surface::FlatWater;
 use rust_physics_engine::GRAVITY;
 use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
@@ -29,11 +31,15 @@ impl BuoyancyDemo {
         let mut particle = Particle::new();
         particle
             .set_position(Vec3::from_values(0.0, 0.0, 15.0))
-            .set_damping(1.0);
+            .set_damping(1.0)
+            .set_mass(500.0);
+        // a cubic meter half as dense as the water, it floats half submerged
+        let mut buoyancy_fg = Buoyancy::with_water(1.0, 1.0, WATER_DENSITY, FlatWater::new(-1.0));
+        buoyancy_fg.set_sphere_water_drag();
         BuoyancyDemo {
             window,
             particle,
-            buoyancy_fg: Buoyancy::new(1.0, 1.0, -1.0, 20.0),
+            buoyancy_fg,
             gravity_fg: Gravity::new(GRAVITY),
             log: OpenOptions::new()
                 .write(true)
@@ -56,9 +62,6 @@ impl App for BuoyancyDemo {
         self.gravity_fg.update_force(&mut self.particle, duration);
         let vel = self.particle.get_velocity();
         let pos = self.particle.get_position();
-        if pos.y > 0.0 {
-            panic!("haha");
-        }
         writeln!(self.log, "{}", format!("Velocity {}", vel.y));
         writeln!(self.log, "{}", format!("Position {}", pos.y));
         writeln!(self.log, "");
@@ -0,0 +1,213 @@
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// Standard gravity used by the atmosphere model, in m / s^2
+const STANDARD_GRAVITY: Real = 9.80665;
+/// Specific gas constant of dry air, in J / (kg K)
+pub const AIR_GAS_CONSTANT: Real = 287.053;
+/// Earth radius used to turn geometric altitude into geopotential one, in meters
+const EARTH_RADIUS: Real = 6_356_766.0;
+const SEA_LEVEL_TEMPERATURE: Real = 288.15;
+const SEA_LEVEL_PRESSURE: Real = 101_325.0;
+
+// (base geopotential altitude in m, temperature lapse rate in K / m) of the ISA layers
+const LAYERS: [(Real, Real); 7] = [
+    (0.0, -0.0065),
+    (11_000.0, 0.0),
+    (20_000.0, 0.001),
+    (32_000.0, 0.0028),
+    (47_000.0, 0.0),
+    (51_000.0, -0.0028),
+    (71_000.0, -0.002),
+];
+// the model ends here, the air above is treated as isothermal
+const TOP_ALTITUDE: Real = 84_852.0;
+
+/// Air properties by position, used by air buoyancy and drag
+pub trait Atmosphere {
+    /// Height above sea level, in meters
+    fn get_altitude(&self, position: &Vec3) -> Real {
+        position.y
+    }
+
+    /// In kelvins
+    fn get_temperature(&self, altitude: Real) -> Real;
+
+    /// In pascals
+    fn get_pressure(&self, altitude: Real) -> Real;
+
+    /// In kg / m^3, from the ideal gas law
+    fn get_density(&self, altitude: Real) -> Real {
+        self.get_pressure(altitude) / (AIR_GAS_CONSTANT * self.get_temperature(altitude))
+    }
+}
+
+/// International Standard Atmosphere, dry air from sea level up to 86 km. Below sea level
+/// the lowest layer continues, so the air keeps getting warmer and denser
+pub struct StandardAtmosphere {
+    // y coordinate of the sea level in the world
+    sea_level_height: Real,
+    // (temperature, pressure) at the base of every layer
+    layer_bases: [(Real, Real); 7],
+}
+
+impl StandardAtmosphere {
+    pub fn new(sea_level_height: Real) -> Self {
+        let mut layer_bases = [(SEA_LEVEL_TEMPERATURE, SEA_LEVEL_PRESSURE); 7];
+        for i in 1..LAYERS.len() {
+            let (temperature, pressure) = layer_bases[i - 1];
+            let (base, lapse_rate) = LAYERS[i - 1];
+            let height = LAYERS[i].0 - base;
+            layer_bases[i] = (
+                temperature + lapse_rate * height,
+                layer_pressure(temperature, pressure, lapse_rate, height),
+            );
+        }
+        Self {
+            sea_level_height,
+            layer_bases,
+        }
+    }
+
+    /// The layer index and the geopotential height above its base
+    fn find_layer(&self, altitude: Real) -> (usize, Real) {
+        let geopotential = EARTH_RADIUS * altitude / (EARTH_RADIUS + altitude);
+        let layer = LAYERS
+            .iter()
+            .rposition(|&(base, _)| geopotential >= base)
+            .unwrap_or(0);
+        (layer, geopotential - LAYERS[layer].0)
+    }
+}
+
+impl Default for StandardAtmosphere {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+impl Atmosphere for StandardAtmosphere {
+    fn get_altitude(&self, position: &Vec3) -> Real {
+        position.y - self.sea_level_height
+    }
+
+    fn get_temperature(&self, altitude: Real) -> Real {
+        let (layer, height) = self.find_layer(altitude);
+        let height = height.min(TOP_ALTITUDE - LAYERS[layer].0);
+        self.layer_bases[layer].0 + LAYERS[layer].1 * height
+    }
+
+    fn get_pressure(&self, altitude: Real) -> Real {
+        let (layer, height) = self.find_layer(altitude);
+        let (temperature, pressure) = self.layer_bases[layer];
+        let lapse_rate = LAYERS[layer].1;
+        let layer_height = height.min(TOP_ALTITUDE - LAYERS[layer].0);
+        let pressure = layer_pressure(temperature, pressure, lapse_rate, layer_height);
+        if height > layer_height {
+            let top_temperature = temperature + lapse_rate * layer_height;
+            return layer_pressure(top_temperature, pressure, 0.0, height - layer_height);
+        }
+        pressure
+    }
+}
+
+/// Isothermal air whose density falls exponentially with the altitude, for when the
+/// standard atmosphere is more than needed. Zero density loss keeps the density constant
+pub struct ExponentialAtmosphere {
+    // y coordinate of the sea level in the world
+    sea_level_height: Real,
+    // kg per cubic meter
+    sea_level_density: Real,
+    // the inverse of the height at which the density drops e times, about 1.2e-4 for the Earth
+    density_loss_coefficient: Real,
+}
+
+impl ExponentialAtmosphere {
+    pub fn new(
+        sea_level_height: Real,
+        sea_level_density: Real,
+        density_loss_coefficient: Real,
+    ) -> Self {
+        Self {
+            sea_level_height,
+            sea_level_density,
+            density_loss_coefficient,
+        }
+    }
+}
+
+impl Atmosphere for ExponentialAtmosphere {
+    fn get_altitude(&self, position: &Vec3) -> Real {
+        position.y - self.sea_level_height
+    }
+
+    fn get_temperature(&self, _altitude: Real) -> Real {
+        SEA_LEVEL_TEMPERATURE
+    }
+
+    fn get_pressure(&self, altitude: Real) -> Real {
+        self.get_density(altitude) * AIR_GAS_CONSTANT * SEA_LEVEL_TEMPERATURE
+    }
+
+    fn get_density(&self, altitude: Real) -> Real {
+        self.sea_level_density * (-self.density_loss_coefficient * altitude).exp()
+    }
+}
+
+/// Barometric formula, the pressure at the height above the base of a layer
+fn layer_pressure(
+    base_temperature: Real,
+    base_pressure: Real,
+    lapse_rate: Real,
+    height: Real,
+) -> Real {
+    if lapse_rate == 0.0 {
+        let scale_height = AIR_GAS_CONSTANT * base_temperature / STANDARD_GRAVITY;
+        base_pressure * (-height / scale_height).exp()
+    } else {
+        let temperature = base_temperature + lapse_rate * height;
+        base_pressure
+            * (base_temperature / temperature)
+                .powf(STANDARD_GRAVITY / (AIR_GAS_CONSTANT * lapse_rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: Real, expected: Real) {
+        assert!(
+            (value - expected).abs() < 1.0e-3 * expected,
+            "{} {}",
+            value,
+            expected
+        );
+    }
+
+    #[test]
+    fn standard_atmosphere_at_sea_level() {
+        let atmosphere = StandardAtmosphere::default();
+        assert_close(atmosphere.get_temperature(0.0), 288.15);
+        assert_close(atmosphere.get_pressure(0.0), 101_325.0);
+        assert_close(atmosphere.get_density(0.0), 1.225);
+    }
+
+    #[test]
+    fn standard_atmosphere_at_11_km() {
+        let atmosphere = StandardAtmosphere::default();
+        // geometric altitude, a bit below the tropopause at 11 km of geopotential one
+        assert_close(atmosphere.get_temperature(11_000.0), 216.774);
+        assert_close(atmosphere.get_pressure(11_000.0), 22_699.9);
+        assert_close(atmosphere.get_density(11_000.0), 0.364_801);
+    }
+
+    #[test]
+    fn sea_level_height_shifts_the_altitude() {
+        let atmosphere = StandardAtmosphere::new(-100.0);
+        assert_eq!(
+            atmosphere.get_altitude(&Vec3::from_values(5.0, 0.0, 5.0)),
+            100.0
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::rigid_body::force_generator::ForceGenerator as BodyForceGenerator;
+use crate::rigid_body::RigidBody;
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// Speed of sound in the air, the default speed of the shockwave
+const SOUND_SPEED: Real = 343.0;
+
+/// An explosion in three phases, see Millington, "Game Physics Engine Development".
+/// First the implosion pulls nearby objects in, then a shockwave expands from the
+/// detonation point and pushes objects it passes, at the same time hot gases rise in a
+/// chimney above it. Phases are timed by the simulation time given with set_time
+pub struct Explosion {
+    detonation: Vec3,
+    detonation_time: Real,
+    // the direction the convection chimney rises
+    up: Vec3,
+    time: Real,
+    // the time of the previous step, the shockwave front sweeps from where it was then
+    previous_time: Real,
+
+    // objects closer than the min radius are already in the blast and not pulled
+    implosion_min_radius: Real,
+    implosion_max_radius: Real,
+    implosion_duration: Real,
+    implosion_force: Real,
+
+    shockwave_speed: Real,
+    // objects the front passed during the step and those within half of the thickness
+    // around it get the full force, it falls to zero over another half of the thickness
+    shockwave_thickness: Real,
+    // at the detonation point, it falls linearly to zero during the concussion
+    peak_concussion_force: Real,
+    concussion_duration: Real,
+
+    chimney_radius: Real,
+    chimney_height: Real,
+    // on the chimney axis at the detonation point, it falls to zero at the chimney walls,
+    // top and the end of the convection
+    peak_convection_force: Real,
+    convection_duration: Real,
+}
+
+impl Explosion {
+    /// The implosion and convection are off until they are set
+    pub fn new(detonation: Vec3, detonation_time: Real) -> Self {
+        Explosion {
+            detonation,
+            detonation_time,
+            up: Vec3::from_values(0.0, 1.0, 0.0),
+            time: 0.0,
+            previous_time: 0.0,
+            implosion_min_radius: 0.0,
+            implosion_max_radius: 0.0,
+            implosion_duration: 0.0,
+            implosion_force: 0.0,
+            shockwave_speed: SOUND_SPEED,
+            shockwave_thickness: 1.0,
+            peak_concussion_force: 1000.0,
+            concussion_duration: 1.0,
+            chimney_radius: 0.0,
+            chimney_height: 0.0,
+            peak_convection_force: 0.0,
+            convection_duration: 0.0,
+        }
+    }
+
+    pub fn set_detonation(&mut self, detonation: Vec3, detonation_time: Real) -> &mut Self {
+        self.detonation = detonation;
+        self.detonation_time = detonation_time;
+        self
+    }
+
+    pub fn set_up(&mut self, mut up: Vec3) -> &mut Self {
+        up.normalize();
+        self.up = up;
+        self
+    }
+
+    pub fn set_implosion(
+        &mut self,
+        min_radius: Real,
+        max_radius: Real,
+        duration: Real,
+        force: Real,
+    ) -> &mut Self {
+        self.implosion_min_radius = min_radius;
+        self.implosion_max_radius = max_radius;
+        self.implosion_duration = duration;
+        self.implosion_force = force;
+        self
+    }
+
+    pub fn set_shockwave(
+        &mut self,
+        speed: Real,
+        thickness: Real,
+        peak_force: Real,
+        duration: Real,
+    ) -> &mut Self {
+        self.shockwave_speed = speed;
+        self.shockwave_thickness = thickness;
+        self.peak_concussion_force = peak_force;
+        self.concussion_duration = duration;
+        self
+    }
+
+    pub fn set_convection(
+        &mut self,
+        chimney_radius: Real,
+        chimney_height: Real,
+        peak_force: Real,
+        duration: Real,
+    ) -> &mut Self {
+        self.chimney_radius = chimney_radius;
+        self.chimney_height = chimney_height;
+        self.peak_convection_force = peak_force;
+        self.convection_duration = duration;
+        self
+    }
+
+    /// Both particle and rigid body generator traits have it, this one is used by both.
+    /// It should be called once per step, the step starts at the previous time
+    pub fn set_time(&mut self, time: Real) {
+        self.previous_time = self.time.min(time);
+        self.time = time;
+    }
+
+    /// The radius of the shockwave front, None when there is no shockwave now
+    pub fn get_shockwave_radius(&self) -> Option<Real> {
+        let time = self.time - self.detonation_time - self.implosion_duration;
+        if time < 0.0 || time >= self.concussion_duration {
+            return None;
+        }
+        Some(self.shockwave_speed * time)
+    }
+
+    /// The force of the explosion on an object at the position now
+    pub fn get_force(&self, position: &Vec3) -> Vec3 {
+        let mut force = Vec3::new();
+        let time = self.time - self.detonation_time;
+        if time < 0.0 {
+            return force;
+        }
+        let offset = *position - self.detonation;
+        let distance = offset.magnitude();
+        let mut direction = offset;
+        direction.normalize();
+
+        if time < self.implosion_duration {
+            if distance >= self.implosion_min_radius && distance <= self.implosion_max_radius {
+                force.add_scaled(&direction, -self.implosion_force);
+            }
+            return force;
+        }
+
+        let time = time - self.implosion_duration;
+        let previous_time =
+            (self.previous_time - self.detonation_time - self.implosion_duration).max(0.0);
+        // the step may end after the concussion, the front still sweeps to its last radius
+        if previous_time < self.concussion_duration {
+            let end_time = time.min(self.concussion_duration);
+            let start_time = previous_time.min(end_time);
+            // a fast front moves further than its thickness during a long step,
+            // objects it jumped over are hit as well
+            let previous_radius = self.shockwave_speed * start_time;
+            let radius = self.shockwave_speed * end_time;
+            let outside = (previous_radius - distance).max(distance - radius).max(0.0);
+            let half_thickness = 0.5 * self.shockwave_thickness;
+            let from_shell = (outside - half_thickness).max(0.0);
+            if from_shell < half_thickness {
+                // faded to the moment the front reached the object
+                let hit_time = if self.shockwave_speed > 0.0 {
+                    (distance / self.shockwave_speed).clamp(start_time, end_time)
+                } else {
+                    end_time
+                };
+                let fade = 1.0 - hit_time / self.concussion_duration;
+                let profile = if half_thickness > 0.0 {
+                    1.0 - from_shell / half_thickness
+                } else {
+                    1.0
+                };
+                force.add_scaled(&direction, self.peak_concussion_force * fade * profile);
+            }
+        }
+
+        if time < self.convection_duration {
+            let height = &offset * &self.up;
+            let mut across = offset;
+            across.add_scaled(&self.up, -height);
+            let radius = across.magnitude();
+            if height >= 0.0 && height < self.chimney_height && radius < self.chimney_radius {
+                let strength = self.peak_convection_force
+                    * (1.0 - time / self.convection_duration)
+                    * (1.0 - radius / self.chimney_radius)
+                    * (1.0 - height / self.chimney_height);
+                // the rising gases draw the air in at the base of the chimney
+                let mut flow = self.up;
+                flow.add_scaled(
+                    &across,
+                    -(1.0 - height / self.chimney_height) / self.chimney_radius,
+                );
+                force.add_scaled(&flow, strength);
+            }
+        }
+        force
+    }
+}
+
+impl ForceGenerator for Explosion {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
+        if particle.is_infinite_mass() {
+            return;
+        }
+        particle.add_force(self.get_force(&particle.get_position()));
+    }
+
+    fn set_time(&mut self, time: Real) {
+        Explosion::set_time(self, time);
+    }
+}
+
+impl BodyForceGenerator for Explosion {
+    fn update_force(&mut self, body: &mut RigidBody, _duration: Real) {
+        if body.is_infinite_mass() {
+            return;
+        }
+        body.add_force(&self.get_force(&body.get_position()));
+    }
+
+    fn set_time(&mut self, time: Real) {
+        Explosion::set_time(self, time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn explosion() -> Explosion {
+        let mut explosion = Explosion::new(Vec3::new(), 1.0);
+        explosion
+            .set_implosion(1.0, 5.0, 0.5, 100.0)
+            .set_shockwave(10.0, 1.0, 1000.0, 2.0)
+            .set_convection(2.0, 10.0, 50.0, 4.0);
+        explosion
+    }
+
+    fn force_at(explosion: &mut Explosion, time: Real, position: &Vec3) -> Vec3 {
+        explosion.set_time(time - 0.01);
+        explosion.set_time(time);
+        explosion.get_force(position)
+    }
+
+    #[test]
+    fn phases_follow_each_other() {
+        let mut explosion = explosion();
+        let aside = Vec3::from_values(3.0, 0.0, 0.0);
+        assert_eq!(force_at(&mut explosion, 0.9, &aside).magnitude(), 0.0);
+        // pulled in by the implosion
+        assert_eq!(force_at(&mut explosion, 1.2, &aside).x, -100.0);
+
+        // the front reaches the object 0.3 seconds after the implosion
+        let hit = force_at(&mut explosion, 1.8, &aside);
+        assert!((hit.x - 1000.0 * (1.0 - 0.3 / 2.0)).abs() < 1.0);
+        assert_eq!(force_at(&mut explosion, 2.0, &aside).magnitude(), 0.0);
+
+        // rising in the chimney until the convection ends
+        let above = Vec3::from_values(0.0, 5.0, 0.0);
+        let lift = force_at(&mut explosion, 3.5, &above);
+        assert!((lift.y - 50.0 * (1.0 - 2.0 / 4.0) * 0.5).abs() < 1.0e-9);
+        assert_eq!(force_at(&mut explosion, 5.6, &above).magnitude(), 0.0);
+    }
+
+    #[test]
+    fn fast_front_hits_objects_it_jumped_over() {
+        let mut explosion = Explosion::new(Vec3::new(), 0.0);
+        explosion.set_shockwave(1000.0, 1.0, 1000.0, 0.08);
+        explosion.set_time(0.0);
+        // the concussion is over before the step ends, the front stops at 80 meters
+        explosion.set_time(0.1);
+
+        let force = explosion.get_force(&Vec3::from_values(0.0, 0.0, 50.0));
+        assert!((force.z - 1000.0 * (1.0 - 0.05 / 0.08)).abs() < 1.0e-9);
+        let beyond = explosion.get_force(&Vec3::from_values(0.0, 0.0, 90.0));
+        assert_eq!(beyond.magnitude(), 0.0);
+
+        // the next step has no shockwave anymore
+        explosion.set_time(0.2);
+        let force = explosion.get_force(&Vec3::from_values(0.0, 0.0, 50.0));
+        assert_eq!(force.magnitude(), 0.0);
+    }
+}
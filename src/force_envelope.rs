@@ -0,0 +1,444 @@
+use crate::force_field::{update_body_scaled, update_scaled};
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::rigid_body::force_generator::ForceGenerator as BodyForceGenerator;
+use crate::rigid_body::RigidBody;
+use crate::types::Real;
+use std::f64::consts::PI;
+
+/// A curve over the simulation time which scales a force
+pub enum Envelope {
+    /// Changes linearly from one value to another during the time, constant outside of it
+    Ramp {
+        start: Real,
+        duration: Real,
+        from: Real,
+        to: Real,
+    },
+    /// 1 for the width of every period starting from the start time, 0 otherwise
+    Pulse {
+        start: Real,
+        period: Real,
+        width: Real,
+    },
+    /// offset + amplitude * sin(2 pi frequency t + phase)
+    Sine {
+        frequency: Real,
+        phase: Real,
+        amplitude: Real,
+        offset: Real,
+    },
+    /// Linear interpolation between (time, value) keys sorted by time,
+    /// the first and the last values hold outside of them
+    Keyframes(Vec<(Real, Real)>),
+    Custom(Box<dyn Fn(Real) -> Real>),
+}
+
+impl Envelope {
+    pub fn get_value(&self, time: Real) -> Real {
+        match self {
+            Envelope::Ramp {
+                start,
+                duration,
+                from,
+                to,
+            } => {
+                if *duration <= 0.0 {
+                    return if time < *start { *from } else { *to };
+                }
+                let progress = ((time - start) / duration).clamp(0.0, 1.0);
+                from + (to - from) * progress
+            }
+            Envelope::Pulse {
+                start,
+                period,
+                width,
+            } => {
+                if time < *start {
+                    return 0.0;
+                }
+                let since_pulse = if *period > 0.0 {
+                    (time - start) % period
+                } else {
+                    time - start
+                };
+                if since_pulse < *width {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Envelope::Sine {
+                frequency,
+                phase,
+                amplitude,
+                offset,
+            } => offset + amplitude * (2.0 * PI as Real * frequency * time + phase).sin(),
+            Envelope::Keyframes(keys) => {
+                let next = keys.iter().position(|&(key_time, _)| key_time > time);
+                match next {
+                    None => keys.last().map_or(0.0, |&(_, value)| value),
+                    Some(0) => keys[0].1,
+                    Some(i) => {
+                        let (time_a, value_a) = keys[i - 1];
+                        let (time_b, value_b) = keys[i];
+                        value_a + (value_b - value_a) * (time - time_a) / (time_b - time_a)
+                    }
+                }
+            }
+            Envelope::Custom(function) => function(time),
+        }
+    }
+}
+
+/// Scales the force of the generator by the envelope at the time given with set_time
+pub struct Enveloped<G> {
+    generator: G,
+    envelope: Envelope,
+    time: Real,
+}
+
+impl<G> Enveloped<G> {
+    pub fn new(generator: G, envelope: Envelope) -> Self {
+        Enveloped {
+            generator,
+            envelope,
+            time: 0.0,
+        }
+    }
+
+    pub fn set_envelope(&mut self, envelope: Envelope) -> &mut Self {
+        self.envelope = envelope;
+        self
+    }
+
+    pub fn get_generator(&self) -> &G {
+        &self.generator
+    }
+
+    pub fn get_generator_mut(&mut self) -> &mut G {
+        &mut self.generator
+    }
+
+    /// The scale of the force now
+    pub fn get_scale(&self) -> Real {
+        self.envelope.get_value(self.time)
+    }
+}
+
+impl<G: ForceGenerator> ForceGenerator for Enveloped<G> {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, duration: Real) {
+        let scale = self.get_scale();
+        if scale == 0.0 {
+            return;
+        }
+        let generator = &mut self.generator;
+        update_scaled(particle, scale, |particle| {
+            generator.update_force(particle, duration)
+        });
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.time = time;
+        ForceGenerator::set_time(&mut self.generator, time);
+    }
+}
+
+impl<G: BodyForceGenerator> BodyForceGenerator for Enveloped<G> {
+    fn update_force(&mut self, body: &mut RigidBody, duration: Real) {
+        let scale = self.get_scale();
+        if scale == 0.0 {
+            return;
+        }
+        let generator = &mut self.generator;
+        update_body_scaled(body, scale, |body| generator.update_force(body, duration));
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.time = time;
+        BodyForceGenerator::set_time(&mut self.generator, time);
+    }
+}
+
+/// The generator works only from the start till the end time
+pub struct TimeWindow<G> {
+    generator: G,
+    start: Real,
+    end: Real,
+    time: Real,
+}
+
+impl<G> TimeWindow<G> {
+    pub fn new(generator: G, start: Real, end: Real) -> Self {
+        TimeWindow {
+            generator,
+            start,
+            end,
+            time: 0.0,
+        }
+    }
+
+    pub fn set_window(&mut self, start: Real, end: Real) -> &mut Self {
+        self.start = start;
+        self.end = end;
+        self
+    }
+
+    pub fn get_generator(&self) -> &G {
+        &self.generator
+    }
+
+    pub fn get_generator_mut(&mut self) -> &mut G {
+        &mut self.generator
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.time >= self.start && self.time < self.end
+    }
+}
+
+impl<G: ForceGenerator> ForceGenerator for TimeWindow<G> {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, duration: Real) {
+        if self.is_active() {
+            self.generator.update_force(particle, duration);
+        }
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.time = time;
+        ForceGenerator::set_time(&mut self.generator, time);
+    }
+}
+
+impl<G: BodyForceGenerator> BodyForceGenerator for TimeWindow<G> {
+    fn update_force(&mut self, body: &mut RigidBody, duration: Real) {
+        if self.is_active() {
+            self.generator.update_force(body, duration);
+        }
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.time = time;
+        BodyForceGenerator::set_time(&mut self.generator, time);
+    }
+}
+
+/// Both generators act together, sums nest to combine more of them
+pub struct Sum<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> Sum<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Sum { first, second }
+    }
+
+    pub fn get_first(&self) -> &A {
+        &self.first
+    }
+
+    pub fn get_first_mut(&mut self) -> &mut A {
+        &mut self.first
+    }
+
+    pub fn get_second(&self) -> &B {
+        &self.second
+    }
+
+    pub fn get_second_mut(&mut self) -> &mut B {
+        &mut self.second
+    }
+}
+
+impl<A: ForceGenerator, B: ForceGenerator> ForceGenerator for Sum<A, B> {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, duration: Real) {
+        self.first.update_force(particle, duration);
+        self.second.update_force(particle, duration);
+    }
+
+    fn set_time(&mut self, time: Real) {
+        ForceGenerator::set_time(&mut self.first, time);
+        ForceGenerator::set_time(&mut self.second, time);
+    }
+}
+
+impl<A: BodyForceGenerator, B: BodyForceGenerator> BodyForceGenerator for Sum<A, B> {
+    fn update_force(&mut self, body: &mut RigidBody, duration: Real) {
+        self.first.update_force(body, duration);
+        self.second.update_force(body, duration);
+    }
+
+    fn set_time(&mut self, time: Real) {
+        BodyForceGenerator::set_time(&mut self.first, time);
+        BodyForceGenerator::set_time(&mut self.second, time);
+    }
+}
+
+/// Applies the generator once, during the step in which the time reaches the trigger
+/// time. Its force is taken as an impulse in N * s and spread over the step, so the
+/// change of the momentum doesn't depend on the step duration
+pub struct Impulse<G> {
+    generator: G,
+    trigger_time: Real,
+    // the time of the previous step, the impulse is given when the trigger time is
+    // between it and the current one
+    previous_time: Real,
+    time: Real,
+}
+
+impl<G> Impulse<G> {
+    pub fn new(generator: G, trigger_time: Real) -> Self {
+        Impulse {
+            generator,
+            trigger_time,
+            previous_time: Real::NEG_INFINITY,
+            time: Real::NEG_INFINITY,
+        }
+    }
+
+    /// Arms the impulse again
+    pub fn set_trigger_time(&mut self, trigger_time: Real) -> &mut Self {
+        self.trigger_time = trigger_time;
+        self.previous_time = Real::NEG_INFINITY;
+        self
+    }
+
+    pub fn get_generator(&self) -> &G {
+        &self.generator
+    }
+
+    pub fn get_generator_mut(&mut self) -> &mut G {
+        &mut self.generator
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.previous_time < self.trigger_time && self.trigger_time <= self.time
+    }
+
+    fn advance(&mut self, time: Real) {
+        self.previous_time = self.time;
+        self.time = time;
+    }
+}
+
+impl<G: ForceGenerator> ForceGenerator for Impulse<G> {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, duration: Real) {
+        if !self.is_triggered() || duration <= 0.0 {
+            return;
+        }
+        let generator = &mut self.generator;
+        update_scaled(particle, 1.0 / duration, |particle| {
+            generator.update_force(particle, duration)
+        });
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.advance(time);
+        ForceGenerator::set_time(&mut self.generator, time);
+    }
+}
+
+impl<G: BodyForceGenerator> BodyForceGenerator for Impulse<G> {
+    fn update_force(&mut self, body: &mut RigidBody, duration: Real) {
+        if !self.is_triggered() || duration <= 0.0 {
+            return;
+        }
+        let generator = &mut self.generator;
+        update_body_scaled(body, 1.0 / duration, |body| {
+            generator.update_force(body, duration)
+        });
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.advance(time);
+        BodyForceGenerator::set_time(&mut self.generator, time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::force_generator::gravity::Gravity;
+    use crate::particle::Particle;
+    use crate::vector::Vec3;
+
+    fn assert_values(envelope: &Envelope, values: &[(Real, Real)]) {
+        for &(time, value) in values.iter() {
+            let actual = envelope.get_value(time);
+            assert!(
+                (actual - value).abs() < 1.0e-9,
+                "{} {} {}",
+                time,
+                actual,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn envelope_values_at_every_phase() {
+        let ramp = Envelope::Ramp {
+            start: 1.0,
+            duration: 2.0,
+            from: 4.0,
+            to: 0.0,
+        };
+        assert_values(
+            &ramp,
+            &[(0.0, 4.0), (1.0, 4.0), (2.5, 1.0), (3.0, 0.0), (9.0, 0.0)],
+        );
+
+        let pulse = Envelope::Pulse {
+            start: 1.0,
+            period: 2.0,
+            width: 0.5,
+        };
+        assert_values(
+            &pulse,
+            &[(0.9, 0.0), (1.0, 1.0), (1.4, 1.0), (1.6, 0.0), (3.2, 1.0)],
+        );
+
+        let sine = Envelope::Sine {
+            frequency: 0.5,
+            phase: 0.0,
+            amplitude: 2.0,
+            offset: 1.0,
+        };
+        assert_values(&sine, &[(0.0, 1.0), (0.5, 3.0), (1.5, -1.0)]);
+
+        let keyframes = Envelope::Keyframes(vec![(1.0, 2.0), (2.0, 6.0), (4.0, 0.0)]);
+        assert_values(
+            &keyframes,
+            &[(0.0, 2.0), (1.5, 4.0), (2.0, 6.0), (3.0, 3.0), (5.0, 0.0)],
+        );
+    }
+
+    #[test]
+    fn impulse_is_given_once_whatever_the_step() {
+        for &duration in [0.01, 0.1 as Real].iter() {
+            let gravity = Gravity::new(Vec3::from_values(0.0, -2.0, 0.0));
+            let mut impulse = Impulse::new(gravity, 0.5);
+            let mut particle = Particle::new();
+            particle.set_damping(1.0);
+            let mut time = 0.0;
+            while time < 1.0 {
+                time += duration;
+                ForceGenerator::set_time(&mut impulse, time);
+                impulse.update_force(&mut particle, duration);
+                particle.integrate(duration);
+            }
+            assert!((particle.get_velocity().y + 2.0).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn time_window_ends_before_its_end_time() {
+        let gravity = Gravity::new(Vec3::from_values(0.0, -2.0, 0.0));
+        let mut window = TimeWindow::new(gravity, 1.0, 2.0);
+        for &(time, active) in [(0.5, false), (1.0, true), (1.9, true), (2.0, false)].iter() {
+            ForceGenerator::set_time(&mut window, time);
+            assert_eq!(window.is_active(), active);
+        }
+    }
+}
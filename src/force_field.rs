@@ -0,0 +1,516 @@
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::plane::Plane;
+use crate::quaternion::Quaternion;
+use crate::rigid_body::force_generator::ForceGenerator as BodyForceGenerator;
+use crate::rigid_body::RigidBody;
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// A region of space a force acts in
+pub trait Volume {
+    /// 0 in the middle of the volume, 1 on its boundary and greater outside of it
+    fn get_relative_distance(&self, position: &Vec3) -> Real;
+
+    fn contains(&self, position: &Vec3) -> bool {
+        self.get_relative_distance(position) <= 1.0
+    }
+}
+
+/// A box of any orientation
+pub struct BoxVolume {
+    center: Vec3,
+    half_extents: Vec3,
+    orientation: Quaternion,
+}
+
+impl BoxVolume {
+    pub fn new(center: Vec3, half_extents: Vec3) -> Self {
+        if half_extents.x <= 0.0 || half_extents.y <= 0.0 || half_extents.z <= 0.0 {
+            panic!("Half extents should be greater then 0");
+        }
+        BoxVolume {
+            center,
+            half_extents,
+            orientation: Quaternion::identity(),
+        }
+    }
+
+    pub fn set_center(&mut self, center: Vec3) -> &mut Self {
+        self.center = center;
+        self
+    }
+
+    /// Rotates the box around its center
+    pub fn set_orientation(&mut self, orientation: Quaternion) -> &mut Self {
+        self.orientation = orientation.normalize();
+        self
+    }
+}
+
+impl Volume for BoxVolume {
+    fn get_relative_distance(&self, position: &Vec3) -> Real {
+        let local = rotate_inverse(&self.orientation, *position - self.center);
+        (local.x / self.half_extents.x)
+            .abs()
+            .max((local.y / self.half_extents.y).abs())
+            .max((local.z / self.half_extents.z).abs())
+    }
+}
+
+pub struct SphereVolume {
+    center: Vec3,
+    radius: Real,
+}
+
+impl SphereVolume {
+    pub fn new(center: Vec3, radius: Real) -> Self {
+        if radius <= 0.0 {
+            panic!("Radius should be greater then 0");
+        }
+        SphereVolume { center, radius }
+    }
+
+    pub fn set_center(&mut self, center: Vec3) -> &mut Self {
+        self.center = center;
+        self
+    }
+}
+
+impl Volume for SphereVolume {
+    fn get_relative_distance(&self, position: &Vec3) -> Real {
+        (*position - self.center).magnitude() / self.radius
+    }
+}
+
+/// A cylinder around the axis going through the center, e.g. a wind tunnel.
+/// The distance to the axis matters for the falloff, not the one along it
+pub struct CylinderVolume {
+    center: Vec3,
+    axis: Vec3,
+    radius: Real,
+    half_length: Real,
+}
+
+impl CylinderVolume {
+    pub fn new(center: Vec3, mut axis: Vec3, radius: Real, length: Real) -> Self {
+        if radius <= 0.0 {
+            panic!("Radius should be greater then 0");
+        }
+        axis.normalize();
+        CylinderVolume {
+            center,
+            axis,
+            radius,
+            half_length: 0.5 * length,
+        }
+    }
+
+    pub fn set_center(&mut self, center: Vec3) -> &mut Self {
+        self.center = center;
+        self
+    }
+}
+
+impl Volume for CylinderVolume {
+    fn get_relative_distance(&self, position: &Vec3) -> Real {
+        let mut offset = *position - self.center;
+        let along = &offset * &self.axis;
+        if along.abs() > self.half_length {
+            return Real::INFINITY;
+        }
+        offset.add_scaled(&self.axis, -along);
+        offset.magnitude() / self.radius
+    }
+}
+
+/// Everything behind the plane. There is no middle, the falloff ends at the depth
+/// below the plane and deeper the force is full
+pub struct HalfSpace {
+    plane: Plane,
+    falloff_depth: Real,
+}
+
+impl HalfSpace {
+    pub fn new(plane: Plane, falloff_depth: Real) -> Self {
+        HalfSpace {
+            plane,
+            falloff_depth,
+        }
+    }
+}
+
+impl Volume for HalfSpace {
+    fn get_relative_distance(&self, position: &Vec3) -> Real {
+        let distance = self.plane.distance(position);
+        if self.falloff_depth <= 0.0 {
+            return if distance <= 0.0 { 0.0 } else { Real::INFINITY };
+        }
+        (1.0 + distance / self.falloff_depth).max(0.0)
+    }
+}
+
+/// How a force weakens from the middle of a volume to its boundary
+pub enum Falloff {
+    /// The full force in the whole volume
+    None,
+    /// From the full force in the middle to zero on the boundary
+    Linear,
+    /// The full force closer than the relative distance given,
+    /// further it falls with the square of the distance. With 0 only the middle gets it
+    InverseSquare(Real),
+    /// Takes the relative distance from 0 in the middle to 1 on the boundary
+    Custom(Box<dyn Fn(Real) -> Real>),
+}
+
+impl Falloff {
+    pub fn get_scale(&self, relative_distance: Real) -> Real {
+        match self {
+            Falloff::None => 1.0,
+            Falloff::Linear => (1.0 - relative_distance).max(0.0),
+            Falloff::InverseSquare(min_distance) => {
+                let min_distance = min_distance.max(0.0);
+                if relative_distance <= min_distance {
+                    return 1.0;
+                }
+                let ratio = min_distance / relative_distance;
+                ratio * ratio
+            }
+            Falloff::Custom(function) => function(relative_distance),
+        }
+    }
+}
+
+/// Restricts any force generator to a volume. The force the generator adds
+/// is scaled by the falloff, outside the volume the generator isn't called
+pub struct VolumeForce<G, V: Volume> {
+    generator: G,
+    volume: V,
+    falloff: Falloff,
+}
+
+impl<G, V: Volume> VolumeForce<G, V> {
+    pub fn new(generator: G, volume: V) -> Self {
+        VolumeForce {
+            generator,
+            volume,
+            falloff: Falloff::None,
+        }
+    }
+
+    pub fn set_falloff(&mut self, falloff: Falloff) -> &mut Self {
+        self.falloff = falloff;
+        self
+    }
+
+    pub fn get_generator(&self) -> &G {
+        &self.generator
+    }
+
+    pub fn get_generator_mut(&mut self) -> &mut G {
+        &mut self.generator
+    }
+
+    pub fn get_volume(&self) -> &V {
+        &self.volume
+    }
+
+    pub fn get_volume_mut(&mut self) -> &mut V {
+        &mut self.volume
+    }
+
+    /// The part of the force applied at the position, 0 outside of the volume
+    pub fn get_scale(&self, position: &Vec3) -> Real {
+        get_volume_scale(&self.volume, &self.falloff, position)
+    }
+}
+
+impl<G: ForceGenerator, V: Volume> ForceGenerator for VolumeForce<G, V> {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, duration: Real) {
+        let scale = self.get_scale(&particle.get_position());
+        if scale == 0.0 {
+            return;
+        }
+        let generator = &mut self.generator;
+        update_scaled(particle, scale, |particle| {
+            generator.update_force(particle, duration)
+        });
+    }
+
+    fn set_time(&mut self, time: Real) {
+        ForceGenerator::set_time(&mut self.generator, time);
+    }
+}
+
+impl<G: BodyForceGenerator, V: Volume> BodyForceGenerator for VolumeForce<G, V> {
+    fn update_force(&mut self, body: &mut RigidBody, duration: Real) {
+        let scale = self.get_scale(&body.get_position());
+        if scale == 0.0 {
+            return;
+        }
+        let generator = &mut self.generator;
+        update_body_scaled(body, scale, |body| generator.update_force(body, duration));
+    }
+
+    fn set_time(&mut self, time: Real) {
+        BodyForceGenerator::set_time(&mut self.generator, time);
+    }
+}
+
+/// Pulls objects towards the center of a sphere, a negative strength pushes them out.
+/// Forces are proportional to mass, like gravity, so all objects move alike
+pub struct PointAttractor {
+    volume: SphereVolume,
+    // acceleration in m / s^2 before the falloff
+    strength: Real,
+    falloff: Falloff,
+}
+
+impl PointAttractor {
+    pub fn new(center: Vec3, radius: Real, strength: Real) -> Self {
+        PointAttractor {
+            volume: SphereVolume::new(center, radius),
+            strength,
+            falloff: Falloff::Linear,
+        }
+    }
+
+    pub fn set_center(&mut self, center: Vec3) -> &mut Self {
+        self.volume.set_center(center);
+        self
+    }
+
+    pub fn set_strength(&mut self, strength: Real) -> &mut Self {
+        self.strength = strength;
+        self
+    }
+
+    pub fn set_falloff(&mut self, falloff: Falloff) -> &mut Self {
+        self.falloff = falloff;
+        self
+    }
+
+    /// The acceleration of objects at the position
+    pub fn get_acceleration(&self, position: &Vec3) -> Vec3 {
+        let scale = get_volume_scale(&self.volume, &self.falloff, position);
+        let mut direction = self.volume.center - *position;
+        direction.normalize();
+        &direction * (self.strength * scale)
+    }
+}
+
+impl ForceGenerator for PointAttractor {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
+        if particle.is_infinite_mass() {
+            return;
+        }
+        let acceleration = self.get_acceleration(&particle.get_position());
+        particle.add_force(&acceleration * particle.get_mass());
+    }
+}
+
+impl BodyForceGenerator for PointAttractor {
+    fn update_force(&mut self, body: &mut RigidBody, _duration: Real) {
+        if body.is_infinite_mass() {
+            return;
+        }
+        let acceleration = self.get_acceleration(&body.get_position());
+        body.add_force(&(&acceleration * body.get_mass()));
+    }
+}
+
+/// Gravity of its own direction inside a volume, e.g. on a space station deck
+pub struct GravityZone<V: Volume> {
+    volume: V,
+    gravity: Vec3,
+    falloff: Falloff,
+}
+
+impl<V: Volume> GravityZone<V> {
+    pub fn new(volume: V, gravity: Vec3) -> Self {
+        GravityZone {
+            volume,
+            gravity,
+            falloff: Falloff::None,
+        }
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vec3) -> &mut Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn set_falloff(&mut self, falloff: Falloff) -> &mut Self {
+        self.falloff = falloff;
+        self
+    }
+
+    pub fn get_volume(&self) -> &V {
+        &self.volume
+    }
+
+    pub fn get_volume_mut(&mut self) -> &mut V {
+        &mut self.volume
+    }
+
+    /// The gravity at the position, zero outside of the zone
+    pub fn get_gravity(&self, position: &Vec3) -> Vec3 {
+        &self.gravity * get_volume_scale(&self.volume, &self.falloff, position)
+    }
+}
+
+impl<V: Volume> ForceGenerator for GravityZone<V> {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
+        if particle.is_infinite_mass() {
+            return;
+        }
+        let gravity = self.get_gravity(&particle.get_position());
+        particle.add_force(&gravity * (particle.get_mass() * particle.get_gravity_scale()));
+    }
+}
+
+impl<V: Volume> BodyForceGenerator for GravityZone<V> {
+    fn update_force(&mut self, body: &mut RigidBody, _duration: Real) {
+        if body.is_infinite_mass() {
+            return;
+        }
+        let gravity = self.get_gravity(&body.get_position());
+        body.add_force(&(&gravity * body.get_mass()));
+    }
+}
+
+/// Runs the update and scales only the force it adds to the particle
+pub(crate) fn update_scaled<P: ParticleTrait>(
+    particle: &mut P,
+    scale: Real,
+    update: impl FnOnce(&mut P),
+) {
+    let force_before = particle.get_force_accum();
+    update(particle);
+    let added = particle.get_force_accum() - force_before;
+    particle.add_force(&added * (scale - 1.0));
+}
+
+/// Runs the update and scales only the force and torque it adds to the body
+pub(crate) fn update_body_scaled(
+    body: &mut RigidBody,
+    scale: Real,
+    update: impl FnOnce(&mut RigidBody),
+) {
+    let force_before = body.get_force_accum();
+    let torque_before = body.get_torque_accum();
+    update(body);
+    let added_force = body.get_force_accum() - force_before;
+    let added_torque = body.get_torque_accum() - torque_before;
+    body.add_force(&(&added_force * (scale - 1.0)));
+    body.add_torque(&(&added_torque * (scale - 1.0)));
+}
+
+fn get_volume_scale<V: Volume>(volume: &V, falloff: &Falloff, position: &Vec3) -> Real {
+    let relative_distance = volume.get_relative_distance(position);
+    if relative_distance > 1.0 {
+        return 0.0;
+    }
+    falloff.get_scale(relative_distance)
+}
+
+/// Rotates the vector back by the unit quaternion, from world space to the local one
+fn rotate_inverse(orientation: &Quaternion, vector: Vec3) -> Vec3 {
+    let axis = Vec3::from_values(-orientation.i, -orientation.j, -orientation.k);
+    let mut twice_cross = axis % vector;
+    twice_cross *= 2.0;
+    let mut result = vector;
+    result.add_scaled(&twice_cross, orientation.r);
+    result += axis % twice_cross;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::force_generator::gravity::Gravity;
+    use crate::particle::Particle;
+
+    fn assert_close(value: Real, expected: Real) {
+        assert!((value - expected).abs() < 1.0e-9, "{} {}", value, expected);
+    }
+
+    #[test]
+    fn falloffs_at_the_middle_and_the_boundary() {
+        assert_close(Falloff::None.get_scale(1.0), 1.0);
+        assert_close(Falloff::Linear.get_scale(0.0), 1.0);
+        assert_close(Falloff::Linear.get_scale(0.75), 0.25);
+        assert_close(Falloff::Linear.get_scale(1.0), 0.0);
+        assert_close(Falloff::InverseSquare(0.5).get_scale(0.25), 1.0);
+        assert_close(Falloff::InverseSquare(0.5).get_scale(1.0), 0.25);
+        assert_close(
+            Falloff::Custom(Box::new(|d| 1.0 - d * d)).get_scale(0.5),
+            0.75,
+        );
+    }
+
+    #[test]
+    fn force_ends_at_the_volume_boundary() {
+        let sphere = SphereVolume::new(Vec3::from_values(1.0, 0.0, 0.0), 2.0);
+        let at = |x: Real| Vec3::from_values(x, 0.0, 0.0);
+        assert_close(get_volume_scale(&sphere, &Falloff::None, &at(3.0)), 1.0);
+        assert_close(get_volume_scale(&sphere, &Falloff::None, &at(3.0001)), 0.0);
+        assert_close(get_volume_scale(&sphere, &Falloff::Linear, &at(2.0)), 0.5);
+        assert_close(get_volume_scale(&sphere, &Falloff::Linear, &at(-1.0)), 0.0);
+
+        // half of the falloff depth below the plane
+        let half_space = HalfSpace::new(Plane::new(Vec3::from_values(0.0, 1.0, 0.0), 0.0), 2.0);
+        let below = |y: Real| Vec3::from_values(0.0, y, 0.0);
+        assert_close(
+            get_volume_scale(&half_space, &Falloff::Linear, &below(0.1)),
+            0.0,
+        );
+        assert_close(
+            get_volume_scale(&half_space, &Falloff::Linear, &below(-1.0)),
+            0.5,
+        );
+        assert_close(
+            get_volume_scale(&half_space, &Falloff::Linear, &below(-5.0)),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn rotated_box_boundary() {
+        let mut volume = BoxVolume::new(Vec3::new(), Vec3::from_values(2.0, 1.0, 1.0));
+        // 90 degrees around y, the long side is along z
+        let half_angle = std::f64::consts::FRAC_PI_4 as Real;
+        volume.set_orientation(Quaternion::new(
+            half_angle.cos(),
+            0.0,
+            half_angle.sin(),
+            0.0,
+        ));
+        assert_close(
+            volume.get_relative_distance(&Vec3::from_values(0.0, 0.0, 2.0)),
+            1.0,
+        );
+        assert_close(
+            volume.get_relative_distance(&Vec3::from_values(2.0, 0.0, 0.0)),
+            2.0,
+        );
+    }
+
+    #[test]
+    fn volume_force_is_scaled_by_the_falloff() {
+        let gravity = Gravity::new(Vec3::from_values(0.0, -10.0, 0.0));
+        let mut force = VolumeForce::new(gravity, SphereVolume::new(Vec3::new(), 4.0));
+        force.set_falloff(Falloff::Linear);
+        let mut particle = Particle::new();
+        particle.set_position(Vec3::from_values(0.0, 3.0, 0.0));
+        particle.add_force(Vec3::from_values(1.0, 0.0, 0.0));
+        force.update_force(&mut particle, 0.01);
+        // forces added before aren't scaled
+        assert_close(particle.get_force_accum().x, 1.0);
+        assert_close(particle.get_force_accum().y, -2.5);
+
+        particle.set_position(Vec3::from_values(0.0, 4.5, 0.0));
+        force.update_force(&mut particle, 0.01);
+        assert_close(particle.get_force_accum().y, -2.5);
+    }
+}
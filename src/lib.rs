@@ -1,13 +1,21 @@
 use crate::vector::Vec3;
 
+pub mod atmosphere;
+pub mod explosion;
+pub mod force_envelope;
+pub mod force_field;
 pub mod matrix;
+pub mod noise;
+pub mod orbital_mechanics;
 pub mod particle;
+pub mod plane;
 pub mod quaternion;
 pub mod random;
 pub mod rigid_body;
 pub mod timing;
 pub mod types;
 pub mod vector;
+pub mod water_surface;
 
 pub const GRAVITY: Vec3 = Vec3 {
     x: 0.0,
@@ -11,6 +11,7 @@ const MATRIX_4_SIZE: usize = 12;
 * 3D space that does not include a translational component. This
 * matrix is not padded to produce an aligned structure.
 */
+#[derive(Copy, Clone, Debug)]
 pub struct Matrix3 {
     pub data: [Real; MATRIX_3_SIZE],
 }
@@ -33,10 +34,104 @@ impl Matrix3 {
         }
     }
 
+    pub fn identity() -> Self {
+        let mut result = Matrix3::new();
+        result.data[0] = 1.0;
+        result.data[4] = 1.0;
+        result.data[8] = 1.0;
+        result
+    }
+
+    /// Builds the matrix a * b^T
+    pub fn from_outer_product(a: &Vec3, b: &Vec3) -> Self {
+        Matrix3 {
+            data: [
+                a.x * b.x,
+                a.x * b.y,
+                a.x * b.z,
+                a.y * b.x,
+                a.y * b.y,
+                a.y * b.z,
+                a.z * b.x,
+                a.z * b.y,
+                a.z * b.z,
+            ],
+        }
+    }
+
+    /// Rotation by angle radians around the unit axis, Rodrigues' formula
+    pub fn from_axis_angle(axis: &Vec3, angle: Real) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+        Matrix3 {
+            data: [
+                t * axis.x * axis.x + cos,
+                t * axis.x * axis.y - sin * axis.z,
+                t * axis.x * axis.z + sin * axis.y,
+                t * axis.x * axis.y + sin * axis.z,
+                t * axis.y * axis.y + cos,
+                t * axis.y * axis.z - sin * axis.x,
+                t * axis.x * axis.z - sin * axis.y,
+                t * axis.y * axis.z + sin * axis.x,
+                t * axis.z * axis.z + cos,
+            ],
+        }
+    }
+
+    pub fn get_column(&self, i: usize) -> Vec3 {
+        Vec3::from_values(self.data[i], self.data[3 + i], self.data[6 + i])
+    }
+
+    pub fn set_column(&mut self, i: usize, v: &Vec3) {
+        self.data[i] = v.x;
+        self.data[3 + i] = v.y;
+        self.data[6 + i] = v.z;
+    }
+
     pub fn transform(&self, v: &Vec3) -> Vec3 {
         self * v
     }
 
+    /// Returns the rotation R of the polar decomposition A = RS, the rotation
+    /// closest to this matrix. It's found iteratively, starting from the guess,
+    /// so passing the rotation found during the previous frame makes it converge
+    /// in a couple of iterations. Unlike inverse based methods it copes with
+    /// degenerate (e.g. flat) matrices. See Müller et al., "A Robust Method
+    /// to Extract the Rotational Part of Deformations"
+    pub fn get_polar_rotation(&self, guess: &Matrix3, max_iterations: u32) -> Matrix3 {
+        let mut rotation = *guess;
+        for _ in 0..max_iterations {
+            let mut torque = Vec3::new();
+            let mut alignment = 0.0;
+            for i in 0..3 {
+                let r = rotation.get_column(i);
+                let a = self.get_column(i);
+                torque += r % a;
+                alignment += &r * &a;
+            }
+            let mut axis = &torque * (1.0 / (alignment.abs() + 1.0e-9));
+            let angle = axis.magnitude();
+            if angle < 1.0e-9 {
+                break;
+            }
+            axis.normalize();
+            rotation = &Matrix3::from_axis_angle(&axis, angle) * rotation;
+        }
+        rotation.orthonormalize();
+        rotation
+    }
+
+    /// Removes numerical drift from a rotation matrix with Gram-Schmidt
+    pub fn orthonormalize(&mut self) {
+        let mut x = self.get_column(0);
+        let mut y = self.get_column(1);
+        let mut z = Vec3::new();
+        Vec3::make_orthogonal_basis(&mut x, &mut y, &mut z);
+        self.set_column(0, &x);
+        self.set_column(1, &y);
+        self.set_column(2, &z);
+    }
+
     pub fn invert(&mut self) {
         if let Some(m) = self.get_inverse() {
             *self = m;
@@ -65,7 +160,7 @@ impl Matrix3 {
         Some(result)
     }
 
-    fn get_determinant(&self) -> Real {
+    pub fn get_determinant(&self) -> Real {
         let t1 = self.data[0] * self.data[4];
         let t2 = self.data[0] * self.data[5];
         let t3 = self.data[1] * self.data[3];
@@ -244,7 +339,7 @@ impl ops::Mul<Matrix3> for &Matrix3 {
         Matrix3 {
             data: [
                 self.data[0] * m.data[0] + self.data[1] * m.data[3] + self.data[2] * m.data[6],
-                self.data[0] * m.data[1] + self.data[1] * self.data[4] + self.data[2] * m.data[7],
+                self.data[0] * m.data[1] + self.data[1] * m.data[4] + self.data[2] * m.data[7],
                 self.data[0] * m.data[2] + self.data[1] * m.data[5] + self.data[2] * m.data[8],
                 self.data[3] * m.data[0] + self.data[4] * m.data[3] + self.data[5] * m.data[6],
                 self.data[3] * m.data[1] + self.data[4] * m.data[4] + self.data[5] * m.data[7],
@@ -257,6 +352,34 @@ impl ops::Mul<Matrix3> for &Matrix3 {
     }
 }
 
+impl ops::Mul<Real> for &Matrix3 {
+    type Output = Matrix3;
+
+    fn mul(self, v: Real) -> Self::Output {
+        let mut result = *self;
+        result.data.iter_mut().for_each(|d| *d *= v);
+        result
+    }
+}
+
+impl ops::Add<Matrix3> for &Matrix3 {
+    type Output = Matrix3;
+
+    fn add(self, m: Matrix3) -> Self::Output {
+        let mut result = *self;
+        result += m;
+        result
+    }
+}
+
+impl ops::AddAssign<Matrix3> for Matrix3 {
+    fn add_assign(&mut self, m: Matrix3) {
+        for (d, v) in self.data.iter_mut().zip(m.data.iter()) {
+            *d += v;
+        }
+    }
+}
+
 impl ops::Mul<&Vec3> for &Matrix4 {
     type Output = Vec3;
 
@@ -343,3 +466,44 @@ impl ops::Mul<Matrix4> for &Matrix4 {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Matrix3, b: &Matrix3, tolerance: Real) {
+        for (x, y) in a.data.iter().zip(b.data.iter()) {
+            assert!((x - y).abs() < tolerance, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn rotation() -> Matrix3 {
+        let mut axis = Vec3::from_values(1.0, 2.0, 3.0);
+        axis.normalize();
+        Matrix3::from_axis_angle(&axis, 1.0)
+    }
+
+    #[test]
+    fn polar_rotation_of_a_rotated_stretch() {
+        // A = RS with a symmetric positive definite stretch S
+        let stretch = Matrix3 {
+            data: [2.0, 0.3, 0.0, 0.3, 0.5, 0.1, 0.0, 0.1, 1.5],
+        };
+        let deformation = &rotation() * stretch;
+        let polar = deformation.get_polar_rotation(&Matrix3::identity(), 100);
+        assert_close(&polar, &rotation(), 1.0e-6);
+        assert!((polar.get_determinant() - 1.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn polar_rotation_of_a_flat_deformation() {
+        // squashed to a plane, it can't be inverted but the rotation is still there
+        let stretch = Matrix3 {
+            data: [1.5, 0.0, 0.0, 0.0, 0.7, 0.0, 0.0, 0.0, 0.0],
+        };
+        let deformation = &rotation() * stretch;
+        assert!(deformation.get_inverse().is_none());
+        let polar = deformation.get_polar_rotation(&Matrix3::identity(), 100);
+        assert_close(&polar, &rotation(), 1.0e-6);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::types::Real;
+
+const PERMUTATION_SIZE: usize = 256;
+
+/// Ken Perlin's improved gradient noise. Smooth pseudo random values from about -1 to 1,
+/// zero at integer coordinates. The same seed always gives the same noise
+pub struct PerlinNoise {
+    // the permutation repeated twice, so indices don't have to wrap
+    permutation: Vec<usize>,
+}
+
+impl PerlinNoise {
+    pub fn new(seed: u64) -> Self {
+        let mut permutation: Vec<usize> = (0..PERMUTATION_SIZE).collect();
+        // Fisher-Yates shuffle with a linear congruential generator, thread_rng can't be seeded
+        let mut state = seed;
+        for i in (1..PERMUTATION_SIZE).rev() {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            let j = (state >> 33) as usize % (i + 1);
+            permutation.swap(i, j);
+        }
+        permutation.extend_from_within(..);
+        Self { permutation }
+    }
+
+    pub fn get(&self, x: Real, y: Real, z: Real) -> Real {
+        let (xi, x) = split(x);
+        let (yi, y) = split(y);
+        let (zi, z) = split(z);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.permutation;
+        let a = p[xi] + yi;
+        let aa = p[a] + zi;
+        let ab = p[a + 1] + zi;
+        let b = p[xi + 1] + yi;
+        let ba = p[b] + zi;
+        let bb = p[b + 1] + zi;
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, gradient(p[aa], x, y, z), gradient(p[ba], x - 1.0, y, z)),
+                lerp(
+                    u,
+                    gradient(p[ab], x, y - 1.0, z),
+                    gradient(p[bb], x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    gradient(p[aa + 1], x, y, z - 1.0),
+                    gradient(p[ba + 1], x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    gradient(p[ab + 1], x, y - 1.0, z - 1.0),
+                    gradient(p[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+}
+
+/// The lattice cell (wrapped to the permutation size) and the position inside it
+fn split(value: Real) -> (usize, Real) {
+    let floor = value.floor();
+    let cell = (floor as i64).rem_euclid(PERMUTATION_SIZE as i64) as usize;
+    (cell, value - floor)
+}
+
+// 6t^5 - 15t^4 + 10t^3, the first and second derivatives are zero at the cell borders
+fn fade(t: Real) -> Real {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: Real, a: Real, b: Real) -> Real {
+    a + t * (b - a)
+}
+
+/// Dot product of the offset with one of 12 gradient directions picked by the hash
+fn gradient(hash: usize, x: Real, y: Real, z: Real) -> Real {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(noise: &PerlinNoise) -> Vec<Real> {
+        (0..1000)
+            .map(|i| {
+                let i = i as Real;
+                noise.get(i * 0.173, i * 0.071 - 20.0, (i * 0.31).sin() * 7.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_noise() {
+        assert_eq!(samples(&PerlinNoise::new(7)), samples(&PerlinNoise::new(7)));
+        assert_ne!(samples(&PerlinNoise::new(7)), samples(&PerlinNoise::new(8)));
+    }
+
+    #[test]
+    fn noise_is_bounded_and_zero_at_integer_coordinates() {
+        let noise = PerlinNoise::new(3);
+        let values = samples(&noise);
+        assert!(values.iter().all(|value| value.abs() <= 1.0));
+        assert!(values.iter().any(|value| value.abs() > 0.3));
+        assert_eq!(noise.get(4.0, -2.0, 300.0), 0.0);
+    }
+}
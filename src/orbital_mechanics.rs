@@ -0,0 +1,452 @@
+use crate::particle::particle_trait::ParticleTrait;
+use crate::types::Real;
+use crate::vector::Vec3;
+use std::f64::consts::PI;
+
+// eccentricities and inclinations below this are treated as zero,
+// angles measured from the periapsis or the node are undefined then
+const ANGLE_EPSILON: Real = 1.0e-11;
+const KEPLER_TOLERANCE: Real = 1.0e-14;
+const KEPLER_MAX_ITERATIONS: u32 = 50;
+
+/// Classical (Keplerian) elements of a two-body orbit around a central body with the
+/// gravitational parameter mu = G * M. The reference plane is XY, the reference
+/// direction is X and the angles are in radians.
+///
+/// For circular orbits the argument of periapsis is 0 and the true anomaly is measured
+/// from the ascending node. For equatorial orbits the longitude of the ascending node
+/// is 0 and angles are measured from X. Parabolic orbits (eccentricity exactly 1)
+/// have an infinite semi-major axis and can't be described
+#[derive(Copy, Clone, Debug)]
+pub struct OrbitalElements {
+    // negative for hyperbolic orbits
+    pub semi_major_axis: Real,
+    pub eccentricity: Real,
+    pub inclination: Real,
+    pub longitude_of_ascending_node: Real,
+    pub argument_of_periapsis: Real,
+    pub true_anomaly: Real,
+}
+
+impl OrbitalElements {
+    pub fn from_state_vectors(position: &Vec3, velocity: &Vec3, mu: Real) -> Self {
+        let r = position.magnitude();
+        let angular_momentum = *position % *velocity;
+        let h = angular_momentum.magnitude();
+        let mut normal = angular_momentum;
+        normal.normalize();
+        // points to the ascending node
+        let node = Vec3::from_values(-angular_momentum.y, angular_momentum.x, 0.0);
+
+        let radial_velocity = position * velocity;
+        let mut eccentricity_vector = position * (velocity.square_magnitude() - mu / r);
+        eccentricity_vector.add_scaled(velocity, -radial_velocity);
+        eccentricity_vector *= 1.0 / mu;
+        let eccentricity = eccentricity_vector.magnitude();
+        let energy = velocity.square_magnitude() / 2.0 - mu / r;
+
+        let equatorial = node.magnitude() <= ANGLE_EPSILON * h;
+        let circular = eccentricity <= ANGLE_EPSILON;
+        let reference = if equatorial {
+            Vec3::from_values(1.0, 0.0, 0.0)
+        } else {
+            node
+        };
+        let periapsis = if circular {
+            reference
+        } else {
+            eccentricity_vector
+        };
+
+        Self {
+            semi_major_axis: -mu / (2.0 * energy),
+            eccentricity,
+            inclination: (angular_momentum.z / h).clamp(-1.0, 1.0).acos(),
+            longitude_of_ascending_node: if equatorial {
+                0.0
+            } else {
+                wrap_angle(node.y.atan2(node.x))
+            },
+            argument_of_periapsis: if circular {
+                0.0
+            } else {
+                angle_between(&reference, &periapsis, &normal)
+            },
+            true_anomaly: angle_between(&periapsis, position, &normal),
+        }
+    }
+
+    /// Returns the position and the velocity relative to the central body
+    pub fn to_state_vectors(&self, mu: Real) -> (Vec3, Vec3) {
+        let p = self.get_semi_latus_rectum();
+        let (sin_anomaly, cos_anomaly) = self.true_anomaly.sin_cos();
+        let r = p / (1.0 + self.eccentricity * cos_anomaly);
+        let speed = (mu / p).sqrt();
+
+        let (sin_node, cos_node) = self.longitude_of_ascending_node.sin_cos();
+        let (sin_periapsis, cos_periapsis) = self.argument_of_periapsis.sin_cos();
+        let (sin_inclination, cos_inclination) = self.inclination.sin_cos();
+        // the directions to the periapsis and 90 degrees ahead of it in the orbital plane
+        let periapsis_direction = Vec3::from_values(
+            cos_node * cos_periapsis - sin_node * sin_periapsis * cos_inclination,
+            sin_node * cos_periapsis + cos_node * sin_periapsis * cos_inclination,
+            sin_periapsis * sin_inclination,
+        );
+        let perpendicular_direction = Vec3::from_values(
+            -cos_node * sin_periapsis - sin_node * cos_periapsis * cos_inclination,
+            -sin_node * sin_periapsis + cos_node * cos_periapsis * cos_inclination,
+            cos_periapsis * sin_inclination,
+        );
+
+        let mut position = &periapsis_direction * (r * cos_anomaly);
+        position.add_scaled(&perpendicular_direction, r * sin_anomaly);
+        let mut velocity = &periapsis_direction * (-speed * sin_anomaly);
+        velocity.add_scaled(
+            &perpendicular_direction,
+            speed * (self.eccentricity + cos_anomaly),
+        );
+        (position, velocity)
+    }
+
+    /// Moves the body along the orbit by the given time, negative time goes backwards
+    pub fn propagate(&self, mu: Real, time: Real) -> Self {
+        let mean_anomaly = self.get_mean_anomaly() + self.get_mean_motion(mu) * time;
+        let anomaly = solve_kepler(mean_anomaly, self.eccentricity);
+        let e = self.eccentricity;
+        let true_anomaly = if e < 1.0 {
+            ((1.0 - e * e).sqrt() * anomaly.sin()).atan2(anomaly.cos() - e)
+        } else {
+            ((e * e - 1.0).sqrt() * anomaly.sinh()).atan2(e - anomaly.cosh())
+        };
+        Self {
+            true_anomaly: wrap_angle(true_anomaly),
+            ..*self
+        }
+    }
+
+    /// Mean anomaly for elliptic orbits, its hyperbolic analogue for hyperbolic ones
+    pub fn get_mean_anomaly(&self) -> Real {
+        let e = self.eccentricity;
+        let (sin_anomaly, cos_anomaly) = self.true_anomaly.sin_cos();
+        if e < 1.0 {
+            let eccentric_anomaly = ((1.0 - e * e).sqrt() * sin_anomaly).atan2(e + cos_anomaly);
+            eccentric_anomaly - e * eccentric_anomaly.sin()
+        } else {
+            let hyperbolic_anomaly =
+                ((e * e - 1.0).sqrt() * sin_anomaly / (1.0 + e * cos_anomaly)).asinh();
+            e * hyperbolic_anomaly.sinh() - hyperbolic_anomaly
+        }
+    }
+
+    /// Radians per second
+    pub fn get_mean_motion(&self, mu: Real) -> Real {
+        (mu / self.semi_major_axis.abs().powi(3)).sqrt()
+    }
+
+    /// None for open (hyperbolic) orbits
+    pub fn get_period(&self, mu: Real) -> Option<Real> {
+        if self.eccentricity >= 1.0 {
+            return None;
+        }
+        Some(2.0 * PI as Real / self.get_mean_motion(mu))
+    }
+
+    pub fn get_semi_latus_rectum(&self) -> Real {
+        self.semi_major_axis * (1.0 - self.eccentricity * self.eccentricity)
+    }
+
+    pub fn get_periapsis(&self) -> Real {
+        self.semi_major_axis * (1.0 - self.eccentricity)
+    }
+
+    /// None for open (hyperbolic) orbits
+    pub fn get_apoapsis(&self) -> Option<Real> {
+        if self.eccentricity >= 1.0 {
+            return None;
+        }
+        Some(self.semi_major_axis * (1.0 + self.eccentricity))
+    }
+
+    /// Orbital energy per unit of mass, negative for closed orbits
+    pub fn get_specific_energy(&self, mu: Real) -> Real {
+        -mu / (2.0 * self.semi_major_axis)
+    }
+}
+
+/// Solves Kepler's equation with Newton's method. For elliptic orbits (eccentricity < 1)
+/// returns the eccentric anomaly E of M = E - e sin(E), for hyperbolic ones the
+/// hyperbolic anomaly H of M = e sinh(H) - H
+pub fn solve_kepler(mean_anomaly: Real, eccentricity: Real) -> Real {
+    let e = eccentricity;
+    if e < 1.0 {
+        // the equation is periodic, the mean anomaly is reduced to -pi..pi
+        let turns = ((mean_anomaly + PI as Real) / (2.0 * PI as Real)).floor();
+        let m = mean_anomaly - turns * 2.0 * PI as Real;
+        let mut anomaly = if e > 0.8 { PI as Real * m.signum() } else { m };
+        for _ in 0..KEPLER_MAX_ITERATIONS {
+            let delta = (anomaly - e * anomaly.sin() - m) / (1.0 - e * anomaly.cos());
+            anomaly -= delta;
+            if delta.abs() <= KEPLER_TOLERANCE * anomaly.abs().max(1.0) {
+                break;
+            }
+        }
+        anomaly + turns * 2.0 * PI as Real
+    } else {
+        let m = mean_anomaly;
+        let mut anomaly = (m / e).asinh();
+        for _ in 0..KEPLER_MAX_ITERATIONS {
+            let delta = (e * anomaly.sinh() - anomaly - m) / (e * anomaly.cosh() - 1.0);
+            anomaly -= delta;
+            if delta.abs() <= KEPLER_TOLERANCE * anomaly.abs().max(1.0) {
+                break;
+            }
+        }
+        anomaly
+    }
+}
+
+/// Analytic two-body propagation of a state relative to the central body
+pub fn propagate_state(position: &Vec3, velocity: &Vec3, mu: Real, time: Real) -> (Vec3, Vec3) {
+    OrbitalElements::from_state_vectors(position, velocity, mu)
+        .propagate(mu, time)
+        .to_state_vectors(mu)
+}
+
+/// Moves the particle around the central body with the leapfrog (kick-drift-kick) method.
+/// Unlike integrate it keeps the orbital energy bounded over millions of steps, so the
+/// particle damping is ignored. Accumulated forces and the particle acceleration are
+/// applied as if they were constant during the step
+pub fn integrate_orbit<P: ParticleTrait>(
+    particle: &mut P,
+    center: &Vec3,
+    mu: Real,
+    duration: Real,
+) {
+    if particle.is_infinite_mass() || duration == 0.0 {
+        return;
+    }
+    let mut other_acceleration = particle.get_acceleration();
+    other_acceleration.add_scaled(&particle.get_force_accum(), particle.get_inverse_mass());
+    let half_step = duration / 2.0;
+
+    let mut velocity = particle.get_velocity();
+    velocity.add_scaled(
+        &central_acceleration(&particle.get_position(), center, mu),
+        half_step,
+    );
+    velocity.add_scaled(&other_acceleration, half_step);
+    let mut position = particle.get_position();
+    position.add_scaled(&velocity, duration);
+    velocity.add_scaled(&central_acceleration(&position, center, mu), half_step);
+    velocity.add_scaled(&other_acceleration, half_step);
+
+    particle.set_position(position);
+    particle.set_velocity(velocity);
+    particle.clear_accumulator();
+}
+
+fn central_acceleration(position: &Vec3, center: &Vec3, mu: Real) -> Vec3 {
+    let offset = *center - *position;
+    let distance = offset.magnitude();
+    if distance == 0.0 {
+        return Vec3::new();
+    }
+    &offset * (mu / distance.powi(3))
+}
+
+/// Angle from one vector to another counterclockwise around the normal, from 0 to 2 pi
+fn angle_between(from: &Vec3, to: &Vec3, normal: &Vec3) -> Real {
+    let sin = normal * &(*from % *to);
+    let cos = from * to;
+    wrap_angle(sin.atan2(cos))
+}
+
+fn wrap_angle(angle: Real) -> Real {
+    let full_turn = 2.0 * PI as Real;
+    let angle = angle % full_turn;
+    if angle < 0.0 {
+        angle + full_turn
+    } else {
+        angle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    const EARTH_MU: Real = 3.986004418e14;
+
+    fn assert_close(actual: Real, expected: Real, tolerance: Real) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn circular_low_earth_orbit_period() {
+        // 400 km above the equatorial radius, about where the ISS flies
+        let radius = 6_778_137.0;
+        let position = Vec3::from_values(radius, 0.0, 0.0);
+        let velocity = Vec3::from_values(0.0, (EARTH_MU / radius).sqrt(), 0.0);
+        let elements = OrbitalElements::from_state_vectors(&position, &velocity, EARTH_MU);
+
+        assert_close(elements.eccentricity, 0.0, 1.0e-12);
+        assert_close(elements.semi_major_axis, radius, 1.0e-3);
+        // 92.56 minutes
+        assert_close(elements.get_period(EARTH_MU).unwrap(), 5553.624, 1.0e-3);
+
+        // after a whole period the body is back where it started
+        let period = elements.get_period(EARTH_MU).unwrap();
+        let (end, _) = propagate_state(&position, &velocity, EARTH_MU, period);
+        assert_close((end - position).magnitude(), 0.0, 1.0e-3);
+    }
+
+    #[test]
+    fn elements_state_round_trip() {
+        let elements = OrbitalElements {
+            semi_major_axis: 9.0e6,
+            eccentricity: 0.3,
+            inclination: 0.5,
+            longitude_of_ascending_node: 1.0,
+            argument_of_periapsis: 2.0,
+            true_anomaly: 0.7,
+        };
+        let (position, velocity) = elements.to_state_vectors(EARTH_MU);
+        let result = OrbitalElements::from_state_vectors(&position, &velocity, EARTH_MU);
+
+        assert_close(result.semi_major_axis, elements.semi_major_axis, 1.0e-4);
+        assert_close(result.eccentricity, elements.eccentricity, 1.0e-12);
+        assert_close(result.inclination, elements.inclination, 1.0e-12);
+        assert_close(
+            result.longitude_of_ascending_node,
+            elements.longitude_of_ascending_node,
+            1.0e-12,
+        );
+        assert_close(
+            result.argument_of_periapsis,
+            elements.argument_of_periapsis,
+            1.0e-12,
+        );
+        assert_close(result.true_anomaly, elements.true_anomaly, 1.0e-12);
+
+        let (position_again, velocity_again) = result.to_state_vectors(EARTH_MU);
+        assert_close((position_again - position).magnitude(), 0.0, 1.0e-6);
+        assert_close((velocity_again - velocity).magnitude(), 0.0, 1.0e-9);
+    }
+
+    #[test]
+    fn kepler_equation_known_solution() {
+        // Vallado, "Fundamentals of Astrodynamics and Applications", example 2-1
+        let mean_anomaly = (235.4 as Real).to_radians();
+        let anomaly = solve_kepler(mean_anomaly, 0.4);
+        assert_close(anomaly.to_degrees(), 220.512_074_767_522, 1.0e-9);
+
+        // the hyperbolic solution satisfies its equation as well
+        let (mean_anomaly, eccentricity) = (2.5, 1.8);
+        let anomaly = solve_kepler(mean_anomaly, eccentricity);
+        assert_close(
+            eccentricity * anomaly.sinh() - anomaly,
+            mean_anomaly,
+            1.0e-12,
+        );
+    }
+
+    // largest relative drift of the energy and of the semi-major axis over whole periods
+    fn leapfrog_drift(elements: &OrbitalElements, periods: u32) -> (Real, Real) {
+        let period = elements.get_period(EARTH_MU).unwrap();
+        let steps_per_period = 2000;
+        let (position, velocity) = elements.to_state_vectors(EARTH_MU);
+        let mut particle = Particle::new();
+        particle.set_position(position);
+        particle.set_velocity(velocity);
+        let energy = elements.get_specific_energy(EARTH_MU);
+
+        let (mut energy_drift, mut axis_drift): (Real, Real) = (0.0, 0.0);
+        for _ in 0..periods * steps_per_period {
+            integrate_orbit(
+                &mut particle,
+                &Vec3::new(),
+                EARTH_MU,
+                period / steps_per_period as Real,
+            );
+            let (position, velocity) = (particle.get_position(), particle.get_velocity());
+            let current = velocity.square_magnitude() / 2.0 - EARTH_MU / position.magnitude();
+            let current_elements =
+                OrbitalElements::from_state_vectors(&position, &velocity, EARTH_MU);
+            energy_drift = energy_drift.max(((current - energy) / energy).abs());
+            axis_drift = axis_drift.max(
+                ((current_elements.semi_major_axis - elements.semi_major_axis)
+                    / elements.semi_major_axis)
+                    .abs(),
+            );
+        }
+        (energy_drift, axis_drift)
+    }
+
+    fn circular_orbit() -> OrbitalElements {
+        OrbitalElements {
+            semi_major_axis: 6_778_137.0,
+            eccentricity: 0.0,
+            inclination: 0.9,
+            longitude_of_ascending_node: 0.3,
+            argument_of_periapsis: 0.0,
+            true_anomaly: 0.0,
+        }
+    }
+
+    fn eccentric_orbit() -> OrbitalElements {
+        OrbitalElements {
+            semi_major_axis: 2.0e7,
+            eccentricity: 0.6,
+            inclination: 0.5,
+            longitude_of_ascending_node: 1.0,
+            argument_of_periapsis: 2.0,
+            true_anomaly: 0.0,
+        }
+    }
+
+    #[test]
+    fn leapfrog_keeps_the_energy_bounded() {
+        for elements in [circular_orbit(), eccentric_orbit()].iter() {
+            // the error over a hundred periods is no bigger than over a few
+            let (short_energy, short_axis) = leapfrog_drift(elements, 3);
+            let (long_energy, long_axis) = leapfrog_drift(elements, 100);
+            assert!(long_energy < 1.0e-4, "{}", long_energy);
+            assert!(long_axis < 1.0e-4, "{}", long_axis);
+            assert!(long_energy < 1.1 * short_energy + 1.0e-12);
+            assert!(long_axis < 1.1 * short_axis + 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn leapfrog_follows_the_analytic_orbit() {
+        for elements in [circular_orbit(), eccentric_orbit()].iter() {
+            let period = elements.get_period(EARTH_MU).unwrap();
+            let steps = 20000;
+            let (position, velocity) = elements.to_state_vectors(EARTH_MU);
+            let mut particle = Particle::new();
+            particle.set_position(position);
+            particle.set_velocity(velocity);
+            for _ in 0..steps {
+                integrate_orbit(
+                    &mut particle,
+                    &Vec3::new(),
+                    EARTH_MU,
+                    2.5 * period / steps as Real,
+                );
+            }
+
+            let (expected, _) = elements
+                .propagate(EARTH_MU, 2.5 * period)
+                .to_state_vectors(EARTH_MU);
+            let error = (particle.get_position() - expected).magnitude();
+            assert!(error < 1.0e-3 * elements.semi_major_axis, "{}", error);
+        }
+    }
+}
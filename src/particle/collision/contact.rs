@@ -2,8 +2,23 @@ use crate::particle::particle_trait::ParticleTrait;
 use crate::types::Real;
 use crate::vector::Vec3;
 
-pub trait ContactGenerator<'a, P: ParticleTrait> {
-    fn add_contact(&'a mut self) -> Option<Contact<'a, P>>;
+/// The contact borrows the generator only while it's alive, so the generator can be
+/// checked or used again after the contacts are resolved
+pub trait ContactGenerator<P: ParticleTrait> {
+    fn add_contact(&mut self) -> Option<Contact<'_, P>>;
+}
+
+/// Is called for every generated contact before it gets resolved. It can change
+/// restitution, friction or surface velocity of the contact, e.g. to make a conveyor belt,
+/// or drop the contact completely by returning false, e.g. for one-way platforms
+pub trait ContactModifier<P: ParticleTrait> {
+    fn modify_contact(&mut self, contact: &mut Contact<P>) -> bool;
+}
+
+impl<P: ParticleTrait, F: FnMut(&mut Contact<P>) -> bool> ContactModifier<P> for F {
+    fn modify_contact(&mut self, contact: &mut Contact<P>) -> bool {
+        self(contact)
+    }
 }
 
 pub struct Contact<'a, P: ParticleTrait> {
@@ -15,6 +30,11 @@ pub struct Contact<'a, P: ParticleTrait> {
     pub contact_normal: Vec3,
     // holds the depth of penetration at the contact.
     pub penetration: Real,
+    // coulomb friction coefficient, zero means frictionless contact
+    pub friction: Real,
+    // the velocity of the contact surface itself, e.g. the belt speed of a conveyor.
+    // Friction drags the first particle towards this velocity
+    pub surface_velocity: Vec3,
 }
 
 impl<'a, P: ParticleTrait> Contact<'a, P> {
@@ -48,14 +68,45 @@ impl<'a, P: ParticleTrait> Contact<'a, P> {
         }
         let delta_velocity = new_sep_velocity - separating_velocity;
         let total_inverse_mass = self.get_total_inverse_mass();
+        if total_inverse_mass <= 0.0 {
+            return;
+        }
         let total_impulse = delta_velocity / total_inverse_mass;
-        let impulse_per_mass = &self.contact_normal * total_impulse;
+        self.apply_impulse(&self.contact_normal * total_impulse);
+        self.resolve_friction(total_impulse);
+    }
+
+    /// Removes relative tangential velocity (taking the surface velocity into account),
+    /// but never more than the coulomb friction cone allows for the given normal impulse
+    fn resolve_friction(&mut self, normal_impulse: Real) {
+        if self.friction <= 0.0 {
+            return;
+        }
+        let mut relative_velocity = self.particles.0.get_velocity() - self.surface_velocity;
+        if let Some(p) = &self.particles.1 {
+            relative_velocity -= p.get_velocity();
+        }
+        let normal_velocity = &relative_velocity * &self.contact_normal;
+        let mut tangent_velocity = relative_velocity - &self.contact_normal * normal_velocity;
+        let tangent_speed = tangent_velocity.magnitude();
+        if tangent_speed <= 0.0 {
+            return;
+        }
+        let mut friction_impulse = tangent_speed / self.get_total_inverse_mass();
+        let max_friction_impulse = self.friction * normal_impulse.abs();
+        if friction_impulse > max_friction_impulse {
+            friction_impulse = max_friction_impulse;
+        }
+        tangent_velocity.normalize();
+        self.apply_impulse(&tangent_velocity * -friction_impulse);
+    }
+
+    fn apply_impulse(&mut self, impulse: Vec3) {
         self.particles.0.set_velocity(
-            self.particles.0.get_velocity()
-                + &impulse_per_mass * self.particles.0.get_inverse_mass(),
+            self.particles.0.get_velocity() + &impulse * self.particles.0.get_inverse_mass(),
         );
         if let Some(p) = &mut self.particles.1 {
-            p.set_velocity(p.get_velocity() + &impulse_per_mass * p.get_inverse_mass());
+            p.set_velocity(p.get_velocity() - &impulse * p.get_inverse_mass());
         };
     }
 
@@ -75,6 +126,9 @@ impl<'a, P: ParticleTrait> Contact<'a, P> {
             return;
         }
         let total_inverse_mass = self.get_total_inverse_mass();
+        if total_inverse_mass <= 0.0 {
+            return;
+        }
         let move_per_mass = &self.contact_normal * (self.penetration / total_inverse_mass);
         // TODO implement append position. Do I need it in trait?
         self.particles.0.set_position(
@@ -93,3 +147,95 @@ impl<'a, P: ParticleTrait> Contact<'a, P> {
             }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    fn particle(mass: Real, velocity: Vec3) -> Particle {
+        let mut particle = Particle::new();
+        particle.set_mass(mass);
+        particle.set_velocity(velocity);
+        particle
+    }
+
+    fn contact<'a>(
+        first: &'a mut Particle,
+        second: Option<&'a mut Particle>,
+    ) -> Contact<'a, Particle> {
+        Contact {
+            particles: (first, second),
+            restitution: 0.5,
+            contact_normal: Vec3::from_values(1.0, 0.0, 0.0),
+            penetration: 0.0,
+            friction: 0.0,
+            surface_velocity: Vec3::new(),
+        }
+    }
+
+    #[test]
+    fn two_particles_get_opposite_impulses() {
+        let mut first = particle(1.0, Vec3::from_values(-2.0, 0.0, 0.0));
+        let mut second = particle(3.0, Vec3::from_values(1.0, 0.0, 0.0));
+        let momentum = &first.get_velocity() * 1.0 + &second.get_velocity() * 3.0;
+        contact(&mut first, Some(&mut second)).resolve(0.0);
+
+        let new_momentum = &first.get_velocity() * 1.0 + &second.get_velocity() * 3.0;
+        assert!((new_momentum - momentum).magnitude() < 1.0e-12);
+        // closing at 3 m/s, they separate at half of it
+        let separating_velocity = first.get_velocity().x - second.get_velocity().x;
+        assert!((separating_velocity - 1.5).abs() < 1.0e-12);
+    }
+
+    // falls onto the ground at 2 m/s while sliding along x at 10 m/s
+    fn sliding_particle() -> Particle {
+        particle(1.0, Vec3::from_values(10.0, -2.0, 0.0))
+    }
+
+    fn ground_contact(particle: &mut Particle, friction: Real) -> Contact<'_, Particle> {
+        let mut contact = contact(particle, None);
+        contact.restitution = 0.0;
+        contact.contact_normal = Vec3::from_values(0.0, 1.0, 0.0);
+        contact.friction = friction;
+        contact
+    }
+
+    #[test]
+    fn friction_is_clamped_to_the_coulomb_cone() {
+        let mut particle = sliding_particle();
+        ground_contact(&mut particle, 0.5).resolve(0.0);
+        // the normal impulse is 2, friction takes at most 0.5 * 2 of the 10 m/s
+        let velocity = particle.get_velocity();
+        assert!((velocity - Vec3::from_values(9.0, 0.0, 0.0)).magnitude() < 1.0e-12);
+
+        // within the cone the sliding stops
+        let mut particle = sliding_particle();
+        ground_contact(&mut particle, 10.0).resolve(0.0);
+        assert!(particle.get_velocity().magnitude() < 1.0e-12);
+    }
+
+    #[test]
+    fn friction_drags_towards_the_surface_velocity() {
+        let mut particle = sliding_particle();
+        let mut contact = ground_contact(&mut particle, 10.0);
+        // a conveyor belt moving along z
+        contact.surface_velocity = Vec3::from_values(0.0, 0.0, 3.0);
+        contact.resolve(0.0);
+        let velocity = particle.get_velocity();
+        assert!((velocity - Vec3::from_values(0.0, 0.0, 3.0)).magnitude() < 1.0e-12);
+    }
+
+    #[test]
+    fn infinite_masses_are_not_moved() {
+        let mut first = particle(1.0, Vec3::new());
+        let mut second = particle(1.0, Vec3::new());
+        first.set_inverse_mass(0.0);
+        second.set_inverse_mass(0.0);
+        let mut contact = contact(&mut first, Some(&mut second));
+        contact.penetration = 0.1;
+        contact.resolve(0.01);
+        assert_eq!(first.get_position().magnitude(), 0.0);
+        assert_eq!(second.get_position().magnitude(), 0.0);
+    }
+}
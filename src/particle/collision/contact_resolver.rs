@@ -1,6 +1,6 @@
 extern crate ordered_float;
 
-use crate::particle::collision::contact::Contact;
+use crate::particle::collision::contact::{Contact, ContactModifier};
 use crate::particle::particle_trait::ParticleTrait;
 use crate::types::Real;
 use ordered_float::OrderedFloat;
@@ -12,6 +12,25 @@ pub struct ContactResolver {
 }
 
 impl ContactResolver {
+    pub fn new(iterations: u32) -> Self {
+        ContactResolver {
+            iterations,
+            velocity_iterations: iterations,
+            position_iterations: iterations,
+        }
+    }
+
+    /// Runs the modifier on every contact first, contacts it rejects are not resolved
+    pub fn resolve_modified_contacts<P: ParticleTrait, M: ContactModifier<P>>(
+        &mut self,
+        mut contacts: Vec<Contact<P>>,
+        modifier: &mut M,
+        duration: Real,
+    ) {
+        contacts.retain_mut(|c| modifier.modify_contact(c));
+        self.resolve_contacts(contacts, duration);
+    }
+
     pub fn resolve_contacts<P: ParticleTrait>(
         &mut self,
         mut contacts: Vec<Contact<P>>,
@@ -34,3 +53,48 @@ impl ContactResolver {
         self.position_iterations = iterations;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+    use crate::vector::Vec3;
+
+    fn falling_particle() -> Particle {
+        let mut particle = Particle::new();
+        particle.set_velocity(Vec3::from_values(0.0, -1.0, 0.0));
+        particle
+    }
+
+    fn contact(particle: &mut Particle, normal: Vec3) -> Contact<'_, Particle> {
+        Contact {
+            particles: (particle, None),
+            restitution: 1.0,
+            contact_normal: normal,
+            penetration: 0.0,
+            friction: 0.0,
+            surface_velocity: Vec3::new(),
+        }
+    }
+
+    #[test]
+    fn modifier_drops_and_changes_contacts() {
+        let mut above = falling_particle();
+        let mut below = falling_particle();
+        below.set_velocity(Vec3::from_values(0.0, 1.0, 0.0));
+        let contacts = vec![
+            contact(&mut above, Vec3::from_values(0.0, 1.0, 0.0)),
+            contact(&mut below, Vec3::from_values(0.0, -1.0, 0.0)),
+        ];
+        // a one-way platform, particles pass through it from below
+        // and don't bounce off its top
+        let mut one_way_platform = |contact: &mut Contact<Particle>| {
+            contact.restitution = 0.0;
+            contact.contact_normal.y > 0.0
+        };
+        ContactResolver::new(2).resolve_modified_contacts(contacts, &mut one_way_platform, 0.01);
+
+        assert_eq!(above.get_velocity().magnitude(), 0.0);
+        assert_eq!(below.get_velocity().y, 1.0);
+    }
+}
@@ -1,74 +1,202 @@
 use crate::particle::collision::contact::{Contact, ContactGenerator};
 use crate::particle::particle_trait::ParticleTrait;
 use crate::types::Real;
+use crate::vector::Vec3;
 
 /**
 * Links connect two particles together, generating a contact if
 * they violate the constraints of their link. It is used
 * for cables and rods, and could be used
-* for springs with a limit to their extension
+* for springs with a limit to their extension.
+* A link with a breaking length snaps when it gets stretched beyond it,
+* after that it doesn't generate contacts anymore
 */
-
-struct Cable<'a, P: ParticleTrait> {
+pub struct Cable<'a, P: ParticleTrait> {
     particles: (&'a mut P, &'a mut P),
     max_length: Real,
     restitution: Real,
+    breaking_length: Option<Real>,
+    is_broken: bool,
 }
 
 impl<'a, P: ParticleTrait> Cable<'a, P> {
+    pub fn new(first: &'a mut P, second: &'a mut P, max_length: Real, restitution: Real) -> Self {
+        Cable {
+            particles: (first, second),
+            max_length,
+            restitution,
+            breaking_length: None,
+            is_broken: false,
+        }
+    }
+
+    /// The cable snaps when stretched beyond the length, it should be greater than the max length
+    pub fn set_breaking_length(&mut self, breaking_length: Option<Real>) -> &mut Self {
+        self.breaking_length = breaking_length;
+        self
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.is_broken
+    }
+
     fn current_length(&self) -> Real {
         (self.particles.0.get_position() - self.particles.1.get_position()).magnitude()
     }
 }
 
-impl<'a, P: ParticleTrait> ContactGenerator<'a, P> for Cable<'a, P> {
-    fn add_contact(&'a mut self) -> Option<Contact<'a, P>> {
+impl<'a, P: ParticleTrait> ContactGenerator<P> for Cable<'a, P> {
+    fn add_contact(&mut self) -> Option<Contact<'_, P>> {
+        if self.is_broken {
+            return None;
+        }
         let length = self.current_length();
         if length <= self.max_length {
             return None;
         }
+        if is_beyond(self.breaking_length, length) {
+            self.is_broken = true;
+            return None;
+        }
         let mut normal = self.particles.1.get_position() - self.particles.0.get_position();
         normal.normalize();
         Some(Contact {
-            particles: (self.particles.0, Some(self.particles.1)),
+            particles: (&mut *self.particles.0, Some(&mut *self.particles.1)),
             restitution: self.restitution,
             contact_normal: normal,
             penetration: length - self.max_length,
+            friction: 0.0,
+            surface_velocity: Vec3::new(),
+        })
+    }
+}
+
+/// Keeps the particles at a fixed distance, pushing them apart as well as pulling them together
+pub struct Rod<'a, P: ParticleTrait> {
+    particles: (&'a mut P, &'a mut P),
+    length: Real,
+    breaking_length: Option<Real>,
+    is_broken: bool,
+}
+
+impl<'a, P: ParticleTrait> Rod<'a, P> {
+    pub fn new(first: &'a mut P, second: &'a mut P, length: Real) -> Self {
+        Rod {
+            particles: (first, second),
+            length,
+            breaking_length: None,
+            is_broken: false,
+        }
+    }
+
+    /// The rod snaps when stretched beyond the length, it should be greater than its length
+    pub fn set_breaking_length(&mut self, breaking_length: Option<Real>) -> &mut Self {
+        self.breaking_length = breaking_length;
+        self
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.is_broken
+    }
+
+    fn current_length(&self) -> Real {
+        (self.particles.0.get_position() - self.particles.1.get_position()).magnitude()
+    }
+}
+
+impl<'a, P: ParticleTrait> ContactGenerator<P> for Rod<'a, P> {
+    fn add_contact(&mut self) -> Option<Contact<'_, P>> {
+        if self.is_broken {
+            return None;
+        }
+        let current_length = self.current_length();
+        if current_length == self.length {
+            return None;
+        }
+        if is_beyond(self.breaking_length, current_length) {
+            self.is_broken = true;
+            return None;
+        }
+        let mut normal = self.particles.1.get_position() - self.particles.0.get_position();
+        normal.normalize();
+        let penetration = if current_length > self.length {
+            current_length - self.length
+        } else {
+            normal *= -1.0;
+            self.length - current_length
+        };
+        Some(Contact {
+            particles: (&mut *self.particles.0, Some(&mut *self.particles.1)),
+            restitution: 0.0,
+            contact_normal: normal,
+            penetration,
+            friction: 0.0,
+            surface_velocity: Vec3::new(),
         })
     }
 }
 
-// struct Rod<'a, P: ParticleTrait> {
-//     particles: (&'a mut P, &'a mut P),
-//     length: Real,
-// }
-
-// impl<'a, P: ParticleTrait> Rod<'a, P> {
-//     fn current_length(&self) -> Real {
-//         (self.particles.0.get_position() - self.particles.1.get_position()).magnitude()
-//     }
-// }
-//
-// impl<'a, P: ParticleTrait> ContactGenerator<'a, P> for Rod<'a, P> {
-//     fn add_contact(&mut self) -> Option<Contact<'a, P>> {
-//         let current_length = self.current_length();
-//         if current_length == self.length {
-//             return None;
-//         }
-//         let mut normal = self.particles.1.get_position() - self.particles.0.get_position();
-//         normal.normalize();
-//         let mut penetration = 0.0;
-//         if current_length > self.length {
-//             penetration = current_length - self.length;
-//         } else {
-//             normal *= -1.0;
-//             penetration = self.length - current_length;
-//         }
-//         Some(Contact {
-//             particles: (self.particles.0, Some(self.particles.1)),
-//             restitution: 0.0,
-//             contact_normal: normal,
-//             penetration,
-//         })
-//     }
-// }
+fn is_beyond(breaking_length: Option<Real>, length: Real) -> bool {
+    match breaking_length {
+        Some(breaking_length) => length > breaking_length,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    fn particle(x: Real) -> Particle {
+        let mut particle = Particle::new();
+        particle.set_position(Vec3::from_values(x, 0.0, 0.0));
+        particle
+    }
+
+    fn stretch<P: ParticleTrait>(contact: Option<Contact<P>>, x: Real) {
+        let mut contact = contact.unwrap();
+        let second = contact.particles.1.as_mut().unwrap();
+        second.set_position(Vec3::from_values(x, 0.0, 0.0));
+    }
+
+    #[test]
+    fn cable_snaps_beyond_the_breaking_length() {
+        let (mut first, mut second) = (particle(0.0), particle(1.5));
+        let mut cable = Cable::new(&mut first, &mut second, 1.0, 0.5);
+        cable.set_breaking_length(Some(2.0));
+        stretch(cable.add_contact(), 2.5);
+        assert!(!cable.is_broken());
+
+        assert!(cable.add_contact().is_none());
+        assert!(cable.is_broken());
+        assert!(cable.add_contact().is_none());
+    }
+
+    #[test]
+    fn rod_snaps_beyond_the_breaking_length() {
+        let (mut first, mut second) = (particle(0.0), particle(0.8));
+        let mut rod = Rod::new(&mut first, &mut second, 1.0);
+        rod.set_breaking_length(Some(1.5));
+        // a compressed rod pushes the particles apart
+        let contact = rod.add_contact().unwrap();
+        assert_eq!(contact.contact_normal.x, -1.0);
+        stretch(Some(contact), 1.6);
+        assert!(!rod.is_broken());
+
+        assert!(rod.add_contact().is_none());
+        assert!(rod.is_broken());
+        assert!(rod.add_contact().is_none());
+    }
+
+    #[test]
+    fn unbreakable_cable_keeps_pulling() {
+        let (mut first, mut second) = (particle(0.0), particle(5.0));
+        let mut cable = Cable::new(&mut first, &mut second, 1.0, 0.5);
+        for _ in 0..2 {
+            let contact = cable.add_contact().unwrap();
+            assert_eq!(contact.penetration, 4.0);
+        }
+        assert!(!cable.is_broken());
+    }
+}
@@ -0,0 +1,72 @@
+use crate::random::{random_real, random_unit_vector, random_vector};
+use crate::types::Real;
+use crate::vector::Vec3;
+use std::f64::consts::PI;
+
+/// The region new particles are emitted from, relative to the emitter position
+#[derive(Copy, Clone, Debug)]
+pub enum EmissionShape {
+    // all particles start at the emitter position and fly in random directions
+    Point,
+    // particles start inside the sphere and fly away from its center
+    Sphere { radius: Real },
+    // particles start at the emitter position and fly inside the cone,
+    // angle is the half angle of the cone in radians
+    Cone { direction: Vec3, angle: Real },
+    // particles start inside the box and fly in random directions
+    Box { half_size: Vec3 },
+    // particles start on the disk and fly along its normal
+    Disk { normal: Vec3, radius: Real },
+}
+
+impl EmissionShape {
+    /// Returns a random offset from the emitter position and a unit direction of emission
+    pub fn sample(&self) -> (Vec3, Vec3) {
+        match *self {
+            EmissionShape::Point => (Vec3::new(), random_unit_vector()),
+            EmissionShape::Sphere { radius } => {
+                let direction = random_unit_vector();
+                // cube root keeps the points uniformly distributed over the volume
+                let distance = radius * random_real(0.0, 1.0).cbrt();
+                (&direction * distance, direction)
+            }
+            EmissionShape::Cone { direction, angle } => {
+                let mut axis = direction;
+                axis.normalize();
+                let (u, v) = perpendicular_basis(axis);
+                let cos_theta = random_real(angle.cos(), 1.0);
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let phi = random_real(0.0, 2.0 * PI as Real);
+                let emission_direction =
+                    &axis * cos_theta + &u * (sin_theta * phi.cos()) + &v * (sin_theta * phi.sin());
+                (Vec3::new(), emission_direction)
+            }
+            EmissionShape::Box { half_size } => {
+                let min = Vec3::from_values(-half_size.x, -half_size.y, -half_size.z);
+                (random_vector(min, half_size), random_unit_vector())
+            }
+            EmissionShape::Disk { normal, radius } => {
+                let mut axis = normal;
+                axis.normalize();
+                let (u, v) = perpendicular_basis(axis);
+                let distance = radius * random_real(0.0, 1.0).sqrt();
+                let phi = random_real(0.0, 2.0 * PI as Real);
+                let offset = &u * (distance * phi.cos()) + &v * (distance * phi.sin());
+                (offset, axis)
+            }
+        }
+    }
+}
+
+/// Two unit vectors that are orthogonal to the given normal and to each other
+fn perpendicular_basis(normal: Vec3) -> (Vec3, Vec3) {
+    let helper = if normal.x.abs() < 0.9 {
+        Vec3::from_values(1.0, 0.0, 0.0)
+    } else {
+        Vec3::from_values(0.0, 1.0, 0.0)
+    };
+    let mut u = normal % helper;
+    u.normalize();
+    let v = normal % u;
+    (u, v)
+}
@@ -0,0 +1,214 @@
+pub mod emission_shape;
+pub mod particle_pool;
+pub mod particle_rule;
+
+use crate::particle::emitter::emission_shape::EmissionShape;
+use crate::particle::emitter::particle_pool::{ParticlePool, PooledParticle};
+use crate::particle::emitter::particle_rule::ParticleRule;
+use crate::particle::force_generator::gravity::Gravity;
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::random::{random_real, random_vector};
+use crate::types::Real;
+use crate::vector::Vec3;
+use crate::GRAVITY;
+
+/// Continuously spawns particles of one rule from its shape
+pub struct Emitter {
+    pub position: Vec3,
+    pub shape: EmissionShape,
+    // index of the rule emitted particles are created by
+    pub rule: usize,
+    // particles per second
+    pub spawn_rate: Real,
+    pub enabled: bool,
+    // the fractional part of particles that should have been spawned during previous frames
+    spawn_accumulator: Real,
+}
+
+impl Emitter {
+    pub fn new(rule: usize, position: Vec3, shape: EmissionShape, spawn_rate: Real) -> Self {
+        Self {
+            position,
+            shape,
+            rule,
+            spawn_rate,
+            enabled: true,
+            spawn_accumulator: 0.0,
+        }
+    }
+
+    fn particles_to_spawn(&mut self, duration: Real) -> u32 {
+        if !self.enabled {
+            return 0;
+        }
+        self.spawn_accumulator += self.spawn_rate * duration;
+        let count = self.spawn_accumulator.floor();
+        self.spawn_accumulator -= count;
+        count as u32
+    }
+}
+
+/// Particles with a limited lifetime, created by emitters and by payloads of dying
+/// particles, the way fireworks work
+pub struct ParticleSystem {
+    pool: ParticlePool,
+    rules: Vec<ParticleRule>,
+    emitters: Vec<Emitter>,
+    // gravity by default, every particle gets it scaled by its gravity scale
+    acceleration: Vec3,
+}
+
+impl ParticleSystem {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            pool: ParticlePool::new(capacity),
+            rules: Vec::new(),
+            emitters: Vec::new(),
+            acceleration: GRAVITY,
+        }
+    }
+
+    /// Gravity of the particles, it's scaled by the gravity scale of each one
+    pub fn set_acceleration(&mut self, acceleration: Vec3) -> &mut Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    /// Returns the index the rule should be referenced by from emitters and payloads
+    pub fn add_rule(&mut self, rule: ParticleRule) -> usize {
+        self.rules.push(rule);
+        self.rules.len() - 1
+    }
+
+    pub fn add_emitter(&mut self, emitter: Emitter) -> usize {
+        self.emitters.push(emitter);
+        self.emitters.len() - 1
+    }
+
+    pub fn get_emitter_mut(&mut self, index: usize) -> &mut Emitter {
+        &mut self.emitters[index]
+    }
+
+    pub fn get_pool(&self) -> &ParticlePool {
+        &self.pool
+    }
+
+    pub fn particles(&self) -> impl Iterator<Item = &PooledParticle> {
+        self.pool.iter()
+    }
+
+    /// Spawns count particles from the emitter at once, independently of its spawn rate
+    pub fn burst(&mut self, emitter: usize, count: u32) {
+        let emitter = &self.emitters[emitter];
+        let (rule, position, shape) = (emitter.rule, emitter.position, emitter.shape);
+        for _ in 0..count {
+            self.spawn(rule, position, Vec3::new(), shape);
+        }
+    }
+
+    pub fn update(&mut self, duration: Real) {
+        for i in 0..self.emitters.len() {
+            let count = self.emitters[i].particles_to_spawn(duration);
+            let emitter = &self.emitters[i];
+            let (rule, position, shape) = (emitter.rule, emitter.position, emitter.shape);
+            for _ in 0..count {
+                self.spawn(rule, position, Vec3::new(), shape);
+            }
+        }
+
+        let mut dead = Vec::new();
+        let mut gravity = Gravity::new(self.acceleration);
+        for i in 0..self.pool.slot_count() {
+            let pooled = self.pool.get_mut(i);
+            if !pooled.is_alive() {
+                continue;
+            }
+            gravity.update_force(&mut pooled.particle, duration);
+            pooled.particle.integrate(duration);
+            pooled.lifetime -= duration;
+            if pooled.lifetime <= 0.0 {
+                dead.push(i);
+            }
+        }
+
+        for i in dead {
+            let parent = *self.pool.get(i);
+            // free the slot first, so the payload can reuse it
+            self.pool.kill(i);
+            let payloads = self.rules[parent.rule].payloads.clone();
+            for payload in payloads {
+                let inherited_velocity =
+                    &parent.particle.get_velocity() * self.rules[payload.rule].inherited_velocity;
+                for _ in 0..payload.count {
+                    self.spawn(
+                        payload.rule,
+                        parent.particle.get_position(),
+                        inherited_velocity,
+                        EmissionShape::Point,
+                    );
+                }
+            }
+        }
+    }
+
+    fn spawn(&mut self, rule: usize, position: Vec3, velocity: Vec3, shape: EmissionShape) {
+        let rule_data = &self.rules[rule];
+        let lifetime = random_real(rule_data.min_lifetime, rule_data.max_lifetime);
+        let (offset, direction) = shape.sample();
+        let speed = random_real(rule_data.min_speed, rule_data.max_speed);
+        let velocity = velocity
+            + &direction * speed
+            + random_vector(rule_data.min_velocity, rule_data.max_velocity);
+        let (mass, damping) = (rule_data.mass, rule_data.damping);
+        if let Some(pooled) = self.pool.spawn(rule, lifetime) {
+            pooled.particle.set_mass(mass).set_damping(damping);
+            pooled
+                .particle
+                .set_position(position + offset)
+                .set_velocity(velocity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::emitter::particle_rule::Payload;
+
+    #[test]
+    fn emission_rate() {
+        let mut system = ParticleSystem::new(1000);
+        let rule = system.add_rule(ParticleRule::new(100.0, 100.0));
+        // 2.5 particles a frame, the fractions are carried over
+        system.add_emitter(Emitter::new(rule, Vec3::new(), EmissionShape::Point, 25.0));
+        for frame in 1..=10 {
+            system.update(0.1);
+            assert_eq!(system.get_pool().alive_count(), frame * 5 / 2);
+        }
+        system.get_emitter_mut(0).enabled = false;
+        system.update(0.1);
+        assert_eq!(system.get_pool().alive_count(), 25);
+    }
+
+    #[test]
+    fn lifetime_ends_and_payloads_spawn() {
+        let mut system = ParticleSystem::new(10);
+        let spark = system.add_rule(ParticleRule::new(100.0, 100.0));
+        let shell = system.add_rule(
+            ParticleRule::new(0.15, 0.15)
+                .add_payload(Payload::new(spark, 4))
+                .build(),
+        );
+        let emitter =
+            system.add_emitter(Emitter::new(shell, Vec3::new(), EmissionShape::Point, 0.0));
+        system.burst(emitter, 2);
+        system.update(0.1);
+        assert_eq!(system.get_pool().alive_count(), 2);
+        system.update(0.1);
+        // the shells died, their sparks took the freed slots
+        assert_eq!(system.get_pool().alive_count(), 8);
+        assert!(system.particles().all(|p| p.rule == spark));
+        assert_eq!(system.get_pool().slot_count(), 8);
+    }
+}
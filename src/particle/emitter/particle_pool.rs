@@ -0,0 +1,123 @@
+use crate::particle::Particle;
+use crate::types::Real;
+
+#[derive(Copy, Clone)]
+pub struct PooledParticle {
+    pub particle: Particle,
+    // index of the rule the particle was created by
+    pub rule: usize,
+    // seconds left before the particle dies
+    pub lifetime: Real,
+    alive: bool,
+}
+
+impl PooledParticle {
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+}
+
+/// Fixed size particle storage. Dead particles are not removed, their slots
+/// are reused by new particles, so there are no allocations after warm up
+pub struct ParticlePool {
+    particles: Vec<PooledParticle>,
+    // indices of dead particles
+    free: Vec<usize>,
+    capacity: usize,
+}
+
+impl ParticlePool {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            particles: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Returns a fresh particle, or None if the pool is full
+    pub fn spawn(&mut self, rule: usize, lifetime: Real) -> Option<&mut PooledParticle> {
+        let pooled = PooledParticle {
+            particle: Particle::new(),
+            rule,
+            lifetime,
+            alive: true,
+        };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.particles[index] = pooled;
+                index
+            }
+            None => {
+                if self.particles.len() == self.capacity {
+                    return None;
+                }
+                self.particles.push(pooled);
+                self.particles.len() - 1
+            }
+        };
+        Some(&mut self.particles[index])
+    }
+
+    pub fn kill(&mut self, index: usize) {
+        if self.particles[index].alive {
+            self.particles[index].alive = false;
+            self.free.push(index);
+        }
+    }
+
+    pub fn get(&self, index: usize) -> &PooledParticle {
+        &self.particles[index]
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut PooledParticle {
+        &mut self.particles[index]
+    }
+
+    /// Iterates over alive particles only
+    pub fn iter(&self) -> impl Iterator<Item = &PooledParticle> {
+        self.particles.iter().filter(|p| p.alive)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut PooledParticle> {
+        self.particles.iter_mut().filter(|p| p.alive)
+    }
+
+    // the number of slots ever used, dead particles included
+    pub(crate) fn slot_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn alive_count(&self) -> usize {
+        self.particles.len() - self.free.len()
+    }
+
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_slots_are_reused() {
+        let mut pool = ParticlePool::new(3);
+        for _ in 0..3 {
+            assert!(pool.spawn(0, 1.0).is_some());
+        }
+        assert!(pool.spawn(0, 1.0).is_none());
+
+        pool.kill(1);
+        // killing twice doesn't free the slot twice
+        pool.kill(1);
+        assert_eq!(pool.alive_count(), 2);
+        let reused = pool.spawn(7, 2.0).unwrap();
+        assert_eq!(reused.rule, 7);
+        assert!(pool.spawn(0, 1.0).is_none());
+        assert_eq!(pool.slot_count(), 3);
+        assert_eq!(pool.get(1).rule, 7);
+        assert_eq!(pool.alive_count(), 3);
+    }
+}
@@ -0,0 +1,91 @@
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// Describes particles spawned when a particle of some rule dies
+#[derive(Copy, Clone, Debug)]
+pub struct Payload {
+    // index of the rule the new particles are created by
+    pub rule: usize,
+    pub count: u32,
+}
+
+impl Payload {
+    pub fn new(rule: usize, count: u32) -> Self {
+        Self { rule, count }
+    }
+}
+
+/// Describes how a particle is created and what happens when it dies
+#[derive(Clone, Debug)]
+pub struct ParticleRule {
+    pub(crate) min_lifetime: Real,
+    pub(crate) max_lifetime: Real,
+    // speed along the emission direction
+    pub(crate) min_speed: Real,
+    pub(crate) max_speed: Real,
+    // random velocity added on top of the emission velocity
+    pub(crate) min_velocity: Vec3,
+    pub(crate) max_velocity: Vec3,
+    pub(crate) damping: Real,
+    pub(crate) mass: Real,
+    // the part of the parent velocity payload particles start with
+    pub(crate) inherited_velocity: Real,
+    pub(crate) payloads: Vec<Payload>,
+}
+
+impl ParticleRule {
+    pub fn new(min_lifetime: Real, max_lifetime: Real) -> Self {
+        Self {
+            min_lifetime,
+            max_lifetime,
+            min_speed: 0.0,
+            max_speed: 0.0,
+            min_velocity: Vec3::new(),
+            max_velocity: Vec3::new(),
+            damping: 0.999,
+            mass: 1.0,
+            inherited_velocity: 1.0,
+            payloads: Vec::new(),
+        }
+    }
+
+    pub fn set_speed(&mut self, min_speed: Real, max_speed: Real) -> &mut Self {
+        self.min_speed = min_speed;
+        self.max_speed = max_speed;
+        self
+    }
+
+    pub fn set_velocity(&mut self, min_velocity: Vec3, max_velocity: Vec3) -> &mut Self {
+        self.min_velocity = min_velocity;
+        self.max_velocity = max_velocity;
+        self
+    }
+
+    pub fn set_damping(&mut self, damping: Real) -> &mut Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn set_mass(&mut self, mass: Real) -> &mut Self {
+        if mass <= 0.0 {
+            panic!("Mass should be greater then 0");
+        }
+        self.mass = mass;
+        self
+    }
+
+    pub fn set_inherited_velocity(&mut self, inherited_velocity: Real) -> &mut Self {
+        self.inherited_velocity = inherited_velocity;
+        self
+    }
+
+    pub fn add_payload(&mut self, payload: Payload) -> &mut Self {
+        self.payloads.push(payload);
+        self
+    }
+
+    /// Returns the rule itself instead of a reference, so rules can be built in one expression
+    pub fn build(&mut self) -> Self {
+        self.clone()
+    }
+}
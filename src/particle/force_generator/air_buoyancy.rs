@@ -1,42 +1,114 @@
+use crate::atmosphere::{Atmosphere, ExponentialAtmosphere, StandardAtmosphere};
 use crate::particle::force_generator::ForceGenerator;
 use crate::particle::particle_trait::ParticleTrait;
 use crate::types::Real;
 use crate::vector::Vec3;
+use crate::GRAVITY;
 
-pub struct AirBuoyancy {
-    sea_level_height: Real,
+/// Archimedes' force of the air, the weight of the air displaced by the object.
+/// For a hot-air balloon the particle mass should include the hot air inside
+pub struct AirBuoyancy<A: Atmosphere = StandardAtmosphere> {
+    // cubic meters
     object_volume: Real,
-    // kg per cubic meter
-    sea_level_air_density: Real,
-    // how fast density decreases with height
-    density_loss_coefficient: Real,
+    atmosphere: A,
+    gravity: Vec3,
 }
 
-impl AirBuoyancy {
+impl AirBuoyancy<ExponentialAtmosphere> {
+    /// Air density falls exponentially with the height above the sea level, the density
+    /// loss coefficient is the inverse of the height at which it drops e times, zero keeps
+    /// it constant. Take 1.2e-4 for the Earth air or use with_atmosphere
+    pub fn exponential(
+        sea_level_height: Real,
+        object_volume: Real,
+        sea_level_air_density: Real,
+        density_loss_coefficient: Real,
+    ) -> Self {
+        Self::with_atmosphere(
+            object_volume,
+            ExponentialAtmosphere::new(
+                sea_level_height,
+                sea_level_air_density,
+                density_loss_coefficient,
+            ),
+        )
+    }
+
+    #[deprecated(
+        note = "the density no longer falls as 1 / height, use exponential with a loss coefficient of about 1.2e-4"
+    )]
     pub fn new(
         sea_level_height: Real,
         object_volume: Real,
         sea_level_air_density: Real,
         density_loss_coefficient: Real,
     ) -> Self {
-        AirBuoyancy {
+        Self::exponential(
             sea_level_height,
-            sea_level_air_density,
             object_volume,
+            sea_level_air_density,
             density_loss_coefficient,
+        )
+    }
+}
+
+impl<A: Atmosphere> AirBuoyancy<A> {
+    pub fn with_atmosphere(object_volume: Real, atmosphere: A) -> Self {
+        AirBuoyancy {
+            object_volume,
+            atmosphere,
+            gravity: GRAVITY,
         }
     }
+
+    pub fn set_object_volume(&mut self, object_volume: Real) -> &mut Self {
+        self.object_volume = object_volume;
+        self
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vec3) -> &mut Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn get_atmosphere(&self) -> &A {
+        &self.atmosphere
+    }
 }
 
-impl ForceGenerator for AirBuoyancy {
+impl<A: Atmosphere> ForceGenerator for AirBuoyancy<A> {
     fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
-        // TODO: what if a particle is lower then sea level?
-        particle.add_force(Vec3::from_values(
-            0.0,
-            self.sea_level_air_density
-                / (particle.get_position().y * self.density_loss_coefficient)
-                * self.object_volume,
-            0.0,
-        ));
+        let altitude = self.atmosphere.get_altitude(&particle.get_position());
+        let displaced_mass = self.atmosphere.get_density(altitude) * self.object_volume;
+        particle.add_force(&self.gravity * -displaced_mass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    fn lift<A: Atmosphere>(buoyancy: &mut AirBuoyancy<A>, height: Real) -> Real {
+        let mut particle = Particle::new();
+        particle.set_position(Vec3::from_values(0.0, height, 0.0));
+        buoyancy.update_force(&mut particle, 0.01);
+        particle.get_force_accum().y
+    }
+
+    #[test]
+    fn constant_density_lifts_with_the_weight_of_the_displaced_air() {
+        let mut buoyancy = AirBuoyancy::exponential(0.0, 2.0, 1.2, 0.0);
+        let expected = 1.2 * 2.0 * -GRAVITY.y;
+        assert!((lift(&mut buoyancy, 0.0) - expected).abs() < 1.0e-9);
+        assert!((lift(&mut buoyancy, 5000.0) - expected).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn lift_gets_weaker_with_the_altitude() {
+        let mut standard = AirBuoyancy::with_atmosphere(2.0, StandardAtmosphere::default());
+        let mut exponential = AirBuoyancy::exponential(0.0, 2.0, 1.225, 1.2e-4);
+        assert!(lift(&mut standard, 3000.0) < 0.8 * lift(&mut standard, 0.0));
+        assert!(lift(&mut exponential, 3000.0) < 0.8 * lift(&mut exponential, 0.0));
     }
 }
@@ -3,10 +3,14 @@ use crate::particle::particle_trait::ParticleTrait;
 use crate::types::Real;
 use crate::vector::Vec3;
 
+/// A damped spring between a particle and a fixed point. It's two-sided, a spring
+/// shorter than its rest length pushes the particle away from the anchor
 pub struct AnchoredSpring<'a> {
     anchor: &'a Vec3,
     spring_constant: Real,
     rest_length: Real,
+    // resists the motion along the spring, in N * s / m
+    damping: Real,
 }
 
 impl<'a> AnchoredSpring<'a> {
@@ -15,16 +19,41 @@ impl<'a> AnchoredSpring<'a> {
             anchor,
             spring_constant,
             rest_length,
+            damping: 0.0,
         }
     }
+
+    pub fn set_damping(&mut self, damping: Real) -> &mut Self {
+        self.damping = damping;
+        self
+    }
 }
 
 impl<'a> ForceGenerator for AnchoredSpring<'a> {
     fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
         // calculate the vector of the spring_cube
         let mut force = particle.get_position() - *self.anchor;
-        let magnitude = self.spring_constant * (force.magnitude() - self.rest_length).abs();
+        // a compressed spring pushes the particle away
+        let mut magnitude = self.spring_constant * (force.magnitude() - self.rest_length);
         force.normalize();
+        magnitude += self.damping * (&particle.get_velocity() * &force);
         particle.add_force(&force * -magnitude);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::force_generator::test_helpers::force_at;
+
+    #[test]
+    fn compressed_spring_pushes_away_from_the_anchor() {
+        let anchor = Vec3::new();
+        let mut spring = AnchoredSpring::new(&anchor, 10.0, 2.0);
+        assert_eq!(force_at(&mut spring, 3.0, 0.0), -10.0);
+        assert_eq!(force_at(&mut spring, 1.5, 0.0), 5.0);
+
+        spring.set_damping(2.0);
+        assert_eq!(force_at(&mut spring, 2.0, 1.0), -2.0);
+    }
+}
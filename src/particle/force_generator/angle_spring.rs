@@ -0,0 +1,225 @@
+use crate::particle::particle_trait::ParticleTrait;
+use crate::particle::xpbd::constraint::{bend_angle, dihedral_angle};
+use crate::types::Real;
+use crate::vector::Vec3;
+use std::f64::consts::PI;
+
+/// Resists bending at the middle of three particles, so ropes and hair keep their shape
+/// instead of folding like chains. Particles are referenced by index, all three get
+/// forces and their sum is zero
+pub struct AngleSpring {
+    // the angle is at the middle one
+    particles: [usize; 3],
+    // pi for a straight line
+    rest_angle: Real,
+    // in N * m / rad
+    stiffness: Real,
+    // resists the change of the angle, in N * m * s / rad
+    damping: Real,
+}
+
+impl AngleSpring {
+    pub fn new(particles: [usize; 3], rest_angle: Real, stiffness: Real) -> Self {
+        AngleSpring {
+            particles,
+            rest_angle,
+            stiffness,
+            damping: 0.0,
+        }
+    }
+
+    pub fn set_rest_angle(&mut self, rest_angle: Real) -> &mut Self {
+        self.rest_angle = rest_angle;
+        self
+    }
+
+    pub fn set_damping(&mut self, damping: Real) -> &mut Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn update_forces<P: ParticleTrait>(&self, particles: &mut [P], _duration: Real) {
+        let points = [
+            particles[self.particles[0]].get_position(),
+            particles[self.particles[1]].get_position(),
+            particles[self.particles[2]].get_position(),
+        ];
+        if let Some((angle, gradients)) = bend_angle(&points) {
+            apply_angular_forces(
+                particles,
+                &self.particles,
+                &gradients,
+                angle - self.rest_angle,
+                self.stiffness,
+                self.damping,
+            );
+        }
+    }
+}
+
+/// Resists bending between two triangles sharing an edge, the first one is
+/// (wings.0, edge.0, edge.1), the second one is (wings.1, edge.1, edge.0)
+pub struct DihedralSpring {
+    wings: (usize, usize),
+    edge: (usize, usize),
+    // zero for flat triangles
+    rest_angle: Real,
+    // in N * m / rad
+    stiffness: Real,
+    // resists the change of the angle, in N * m * s / rad
+    damping: Real,
+}
+
+impl DihedralSpring {
+    pub fn new(
+        wings: (usize, usize),
+        edge: (usize, usize),
+        rest_angle: Real,
+        stiffness: Real,
+    ) -> Self {
+        DihedralSpring {
+            wings,
+            edge,
+            rest_angle,
+            stiffness,
+            damping: 0.0,
+        }
+    }
+
+    pub fn set_rest_angle(&mut self, rest_angle: Real) -> &mut Self {
+        self.rest_angle = rest_angle;
+        self
+    }
+
+    pub fn set_damping(&mut self, damping: Real) -> &mut Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn update_forces<P: ParticleTrait>(&self, particles: &mut [P], _duration: Real) {
+        let indices = [self.wings.0, self.wings.1, self.edge.0, self.edge.1];
+        let points = [
+            particles[indices[0]].get_position(),
+            particles[indices[1]].get_position(),
+            particles[indices[2]].get_position(),
+            particles[indices[3]].get_position(),
+        ];
+        if let Some((angle, gradients)) = dihedral_angle(&points) {
+            let mut difference = angle - self.rest_angle;
+            // take the short way around
+            let pi = PI as Real;
+            if difference > pi {
+                difference -= 2.0 * pi;
+            } else if difference < -pi {
+                difference += 2.0 * pi;
+            }
+            apply_angular_forces(
+                particles,
+                &indices,
+                &gradients,
+                difference,
+                self.stiffness,
+                self.damping,
+            );
+        }
+    }
+}
+
+/// Forces of the energy stiffness * difference^2 / 2 and of the damping of the rate
+/// the angle changes with, gradients are of the angle by the particle positions
+fn apply_angular_forces<P: ParticleTrait>(
+    particles: &mut [P],
+    indices: &[usize],
+    gradients: &[Vec3],
+    difference: Real,
+    stiffness: Real,
+    damping: Real,
+) {
+    let angular_velocity: Real = indices
+        .iter()
+        .zip(gradients.iter())
+        .map(|(&i, gradient)| gradient * &particles[i].get_velocity())
+        .sum();
+    let torque = stiffness * difference + damping * angular_velocity;
+    for (&i, gradient) in indices.iter().zip(gradients.iter()) {
+        particles[i].add_force(gradient * -torque);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::force_generator::test_helpers::particle;
+    use crate::particle::Particle;
+
+    fn particles(positions: &[Vec3]) -> Vec<Particle> {
+        positions
+            .iter()
+            .map(|position| particle(*position, Vec3::new()))
+            .collect()
+    }
+
+    fn net_force(particles: &[Particle]) -> Vec3 {
+        let mut force = Vec3::new();
+        for particle in particles.iter() {
+            force += particle.get_force_accum();
+        }
+        force
+    }
+
+    fn simulate<F: Fn(&mut [Particle])>(particles: &mut [Particle], update_forces: F) {
+        for _ in 0..10000 {
+            update_forces(particles);
+            assert!(net_force(particles).magnitude() < 1.0e-9);
+            for particle in particles.iter_mut() {
+                particle.integrate(0.001);
+            }
+        }
+    }
+
+    #[test]
+    fn bent_chain_straightens() {
+        let mut particles = particles(&[
+            Vec3::new(),
+            Vec3::from_values(1.0, 0.0, 0.0),
+            Vec3::from_values(1.0, 1.0, 0.0),
+        ]);
+        let mut spring = AngleSpring::new([0, 1, 2], PI as Real, 1.0);
+        spring.set_damping(2.0);
+        simulate(&mut particles, |particles| {
+            spring.update_forces(particles, 0.001)
+        });
+
+        let u = particles[0].get_position() - particles[1].get_position();
+        let v = particles[2].get_position() - particles[1].get_position();
+        let angle = (u % v).magnitude().atan2(&u * &v);
+        assert!((angle - PI as Real).abs() < 1.0e-2, "{}", angle);
+    }
+
+    #[test]
+    fn folded_quad_unfolds() {
+        // the second triangle is folded up by 90 degrees around the x axis
+        let mut particles = particles(&[
+            Vec3::from_values(0.5, 1.0, 0.0),
+            Vec3::from_values(0.5, 0.0, 1.0),
+            Vec3::new(),
+            Vec3::from_values(1.0, 0.0, 0.0),
+        ]);
+        let mut spring = DihedralSpring::new((0, 1), (2, 3), 0.0, 1.0);
+        spring.set_damping(2.0);
+        let angle = |particles: &[Particle]| {
+            let points = [
+                particles[0].get_position(),
+                particles[1].get_position(),
+                particles[2].get_position(),
+                particles[3].get_position(),
+            ];
+            dihedral_angle(&points).unwrap().0
+        };
+        assert!((angle(&particles).abs() - 0.5 * PI as Real).abs() < 1.0e-9);
+        simulate(&mut particles, |particles| {
+            spring.update_forces(particles, 0.001)
+        });
+        assert!(angle(&particles).abs() < 1.0e-2, "{}", angle(&particles));
+    }
+}
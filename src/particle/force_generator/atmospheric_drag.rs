@@ -0,0 +1,46 @@
+use crate::atmosphere::{Atmosphere, StandardAtmosphere};
+use crate::particle::force_generator::drag::drag_force;
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::types::Real;
+
+/// Pressure drag 1/2 * density * drag coefficient * area * v^2 with the air density at
+/// the particle altitude, so high-altitude projectiles fly further
+pub struct AtmosphericDrag<A: Atmosphere = StandardAtmosphere> {
+    drag_coefficient: Real,
+    // cross-sectional area facing the flow, square meters
+    area: Real,
+    atmosphere: A,
+}
+
+impl<A: Atmosphere> AtmosphericDrag<A> {
+    pub fn new(drag_coefficient: Real, area: Real, atmosphere: A) -> Self {
+        Self {
+            drag_coefficient,
+            area,
+            atmosphere,
+        }
+    }
+
+    pub fn set_drag_coefficient(&mut self, drag_coefficient: Real) -> &mut Self {
+        self.drag_coefficient = drag_coefficient;
+        self
+    }
+
+    pub fn set_area(&mut self, area: Real) -> &mut Self {
+        self.area = area;
+        self
+    }
+
+    pub fn get_atmosphere(&self) -> &A {
+        &self.atmosphere
+    }
+}
+
+impl<A: Atmosphere> ForceGenerator for AtmosphericDrag<A> {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
+        let altitude = self.atmosphere.get_altitude(&particle.get_position());
+        let k2 = 0.5 * self.atmosphere.get_density(altitude) * self.drag_coefficient * self.area;
+        particle.add_force(drag_force(&particle.get_velocity(), 0.0, k2));
+    }
+}
@@ -6,6 +6,8 @@ pub struct Bangee<'a, PT: ParticleTrait> {
     other: &'a PT,
     spring_constant: Real,
     rest_length: Real,
+    // resists the motion along the bungee while it's stretched, in N * s / m
+    damping: Real,
 }
 
 impl<'a, PT: ParticleTrait> Bangee<'a, PT> {
@@ -14,8 +16,14 @@ impl<'a, PT: ParticleTrait> Bangee<'a, PT> {
             other,
             spring_constant,
             rest_length,
+            damping: 0.0,
         }
     }
+
+    pub fn set_damping(&mut self, damping: Real) -> &mut Self {
+        self.damping = damping;
+        self
+    }
 }
 
 impl<'a, PT: ParticleTrait> ForceGenerator for Bangee<'a, PT> {
@@ -31,6 +39,29 @@ impl<'a, PT: ParticleTrait> ForceGenerator for Bangee<'a, PT> {
         magnitude = self.spring_constant * (magnitude - self.rest_length).abs();
         // calculate the final force and apply it
         force.normalize();
-        particle.add_force(&force * -magnitude);
+        let relative_velocity = particle.get_velocity() - self.other.get_velocity();
+        magnitude += self.damping * (&relative_velocity * &force);
+        // damping may only slow the bungee, it can't push the ends apart
+        particle.add_force(&force * -magnitude.max(0.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::force_generator::test_helpers::force_at;
+    use crate::particle::Particle;
+
+    #[test]
+    fn bangee_only_pulls() {
+        let other = Particle::new();
+        let mut bangee = Bangee::new(&other, 10.0, 2.0);
+        bangee.set_damping(5.0);
+        assert_eq!(force_at(&mut bangee, 1.0, -3.0), 0.0);
+        // stretched and moving apart the damping adds to the pull
+        assert_eq!(force_at(&mut bangee, 3.0, 1.0), -15.0);
+        assert_eq!(force_at(&mut bangee, 3.0, -1.0), -5.0);
+        // the ends come together fast, the damping would push them apart
+        assert_eq!(force_at(&mut bangee, 3.0, -4.0), 0.0);
     }
 }
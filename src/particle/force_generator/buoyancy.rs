@@ -1,47 +1,174 @@
+use crate::particle::force_generator::drag::drag_force;
 use crate::particle::force_generator::ForceGenerator;
 use crate::particle::particle_trait::ParticleTrait;
 use crate::types::Real;
-use crate::vector::Vec3;
+use crate::water_surface::{FlatWater, WaterSurface};
+use crate::GRAVITY;
+use std::f64::consts::PI;
 
-pub struct Buoyancy {
-    // the maximum submersion depth of the object before it generates its maximum buoyancy force
-    // it's an approximation to simulate partial object submersion
+// drag coefficient of a sphere
+const SPHERE_DRAG_COEFFICIENT: Real = 0.47;
+
+pub struct Buoyancy<W: WaterSurface = FlatWater> {
+    // the height of the object, it's centered at the particle. An approximation to
+    // simulate partial object submersion, the force grows linearly with the depth
     max_depth: Real,
     object_volume: Real,
-    // the height of the water plane above y = 0. The plane will be parallel to the XZ plane
-    water_height: Real,
     // kg per cubic meter
     liquid_density: Real,
+    water: W,
+    gravity: Real,
+    // multiplies the weight of the displaced liquid, 1 unless made by new
+    force_scale: Real,
+    // drag against the water, scaled by the submerged part of the object
+    water_k1: Real,
+    water_k2: Real,
 }
 
-impl Buoyancy {
+impl Buoyancy<FlatWater> {
+    /// Keeps the meaning it had before water surfaces: the particle is the top of the
+    /// object, it's fully submerged at water_height, and the force is
+    /// liquid_density * object_volume * depth, without gravity. To move to with_water take
+    /// FlatWater::new(water_height + max_depth / 2.0) and the real liquid density, the force
+    /// is then the weight of the displaced liquid, the particle is the object center
     pub fn new(
         max_depth: Real,
         object_volume: Real,
         water_height: Real,
         liquid_density: Real,
+    ) -> Self {
+        let water = FlatWater::new(water_height + max_depth / 2.0);
+        let mut buoyancy = Self::with_water(max_depth, object_volume, liquid_density, water);
+        // the full force is liquid_density * object_volume * max_depth
+        buoyancy.force_scale = max_depth.max(0.0) / buoyancy.gravity;
+        buoyancy
+    }
+}
+
+impl<W: WaterSurface> Buoyancy<W> {
+    /// There is no water drag by default, without it a floating object keeps bobbing
+    pub fn with_water(
+        max_depth: Real,
+        object_volume: Real,
+        liquid_density: Real,
+        water: W,
     ) -> Self {
         Buoyancy {
             max_depth,
             object_volume,
-            water_height,
             liquid_density,
+            water,
+            gravity: GRAVITY.magnitude(),
+            force_scale: 1.0,
+            water_k1: 0.0,
+            water_k2: 0.0,
         }
     }
+
+    pub fn set_water_drag(&mut self, k1: Real, k2: Real) -> &mut Self {
+        self.water_k1 = k1;
+        self.water_k2 = k2;
+        self
+    }
+
+    /// Quadratic water drag of a sphere of the object volume
+    pub fn set_sphere_water_drag(&mut self) -> &mut Self {
+        self.water_k1 = 0.0;
+        self.water_k2 = sphere_water_drag(self.object_volume, self.liquid_density);
+        self
+    }
+
+    pub fn set_gravity(&mut self, gravity: Real) -> &mut Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn get_water(&self) -> &W {
+        &self.water
+    }
+
+    pub fn get_water_mut(&mut self) -> &mut W {
+        &mut self.water
+    }
+
+    /// From 0 above the water to 1 when fully submerged
+    pub fn get_submerged_part<P: ParticleTrait>(&self, particle: &P) -> Real {
+        let depth = self.water.get_depth(&particle.get_position());
+        submerged_part(depth, self.max_depth)
+    }
+}
+
+/// Quadratic drag coefficient of a sphere of the volume moving through the liquid
+pub(crate) fn sphere_water_drag(volume: Real, liquid_density: Real) -> Real {
+    let radius = (3.0 * volume / (4.0 * PI as Real)).cbrt();
+    let area = PI as Real * radius * radius;
+    0.5 * liquid_density * SPHERE_DRAG_COEFFICIENT * area
 }
 
-impl ForceGenerator for Buoyancy {
-    // TODO it doesn't work correctly, if drag force is removed - particle will accelerate more and more
+/// Part of an object max_depth tall centered at the depth which is under the surface
+pub(crate) fn submerged_part(depth: Real, max_depth: Real) -> Real {
+    if max_depth <= 0.0 {
+        return if depth > 0.0 { 1.0 } else { 0.0 };
+    }
+    (depth / max_depth + 0.5).clamp(0.0, 1.0)
+}
+
+impl<W: WaterSurface> ForceGenerator for Buoyancy<W> {
     fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
-        // partial submersion
-        let mut submersion_depth = self.water_height + self.max_depth - particle.get_position().y;
-        if submersion_depth <= 0.0 {
+        let submerged_part = self.get_submerged_part(particle);
+        if submerged_part == 0.0 {
             return;
         }
-        if submersion_depth > self.max_depth {
-            submersion_depth = self.max_depth;
+        let position = particle.get_position();
+        let displaced_weight =
+            self.liquid_density * self.object_volume * self.gravity * self.force_scale;
+        particle.add_force(&self.water.get_up(&position) * (displaced_weight * submerged_part));
+
+        let relative_velocity = particle.get_velocity() - self.water.get_velocity(&position);
+        let drag = drag_force(&relative_velocity, self.water_k1, self.water_k2);
+        particle.add_force(&drag * submerged_part);
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.water.set_time(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+    use crate::vector::Vec3;
+
+    fn force_at<W: WaterSurface>(buoyancy: &mut Buoyancy<W>, height: Real) -> Real {
+        let mut particle = Particle::new();
+        particle.set_position(Vec3::from_values(0.0, height, 0.0));
+        buoyancy.update_force(&mut particle, 0.01);
+        particle.get_force_accum().y
+    }
+
+    #[test]
+    fn new_keeps_the_old_force() {
+        let mut buoyancy = Buoyancy::new(2.0, 3.0, -1.0, 20.0);
+        for &height in [2.0, 1.0, 0.5, -1.0, -5.0 as Real].iter() {
+            let depth = (-1.0 + 2.0 - height).clamp(0.0, 2.0);
+            assert!((force_at(&mut buoyancy, height) - 20.0 * 3.0 * depth).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn floating_object_settles_at_its_draft() {
+        let mut buoyancy = Buoyancy::with_water(1.0, 1.0, 1000.0, FlatWater::new(0.0));
+        buoyancy.set_water_drag(2000.0, 0.0);
+        let mut particle = Particle::new();
+        particle.set_mass(300.0);
+        particle.set_damping(1.0);
+        particle.add_acceleration(GRAVITY);
+        for _ in 0..20000 {
+            buoyancy.update_force(&mut particle, 0.001);
+            particle.integrate(0.001);
         }
-        let force = self.liquid_density * self.object_volume * submersion_depth;
-        particle.add_force(Vec3::from_values(0.0, force, 0.0));
+        // 30% of the object is under the water, the center is 0.2 above the surface
+        assert!((particle.get_position().y - 0.2).abs() < 1.0e-3);
     }
 }
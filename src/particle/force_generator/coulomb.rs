@@ -0,0 +1,149 @@
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::particle::spatial_grid::SpatialGrid;
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// In N m^2 / C^2
+pub const COULOMB_CONSTANT: Real = 8.987_551_792_3e9;
+
+/// Electrostatic attraction and repulsion between charged particles. Positions and charges
+/// are captured by update_sources, then update_force pushes any charged particle away from
+/// the charges of the same sign and pulls it towards the opposite ones
+pub struct Coulomb {
+    coulomb_constant: Real,
+    // charges further away are ignored, the force is simply cut there
+    cutoff: Option<Real>,
+    sources: Vec<(Vec3, Real)>,
+    // only used with a cutoff
+    grid: Option<SpatialGrid>,
+}
+
+impl Coulomb {
+    pub fn new(coulomb_constant: Real) -> Self {
+        Self {
+            coulomb_constant,
+            cutoff: None,
+            sources: Vec::new(),
+            grid: None,
+        }
+    }
+
+    /// Should be called every step before forces are updated
+    pub fn update_sources<P: ParticleTrait>(&mut self, particles: &[P]) {
+        self.sources.clear();
+        for particle in particles.iter() {
+            let charge = particle.get_charge();
+            if charge != 0.0 {
+                self.sources.push((particle.get_position(), charge));
+            }
+        }
+        if let Some(grid) = self.grid.as_mut() {
+            grid.rebuild(self.sources.iter().map(|(position, _)| position));
+        }
+    }
+
+    /// Updates sources and adds electrostatic forces of all particles to each of them
+    pub fn apply_to_all<P: ParticleTrait>(&mut self, particles: &mut [P], duration: Real) {
+        self.update_sources(particles);
+        for particle in particles.iter_mut() {
+            self.update_force(particle, duration);
+        }
+    }
+
+    /// Electric field strength at the position, the force on a charge q is q * E
+    pub fn get_field(&self, position: &Vec3) -> Vec3 {
+        let mut field = Vec3::new();
+        let add_source = |index: usize| {
+            let (source, charge) = self.sources[index];
+            let offset = *position - source;
+            let square_distance = offset.square_magnitude();
+            // the particle itself
+            if square_distance == 0.0 {
+                return;
+            }
+            if let Some(cutoff) = self.cutoff {
+                if square_distance > cutoff * cutoff {
+                    return;
+                }
+            }
+            field.add_scaled(
+                &offset,
+                self.coulomb_constant * charge / (square_distance * square_distance.sqrt()),
+            );
+        };
+        match self.grid.as_ref() {
+            Some(grid) => grid.for_each_candidate(position, add_source),
+            None => (0..self.sources.len()).for_each(add_source),
+        }
+        field
+    }
+
+    pub fn set_coulomb_constant(&mut self, coulomb_constant: Real) -> &mut Self {
+        self.coulomb_constant = coulomb_constant;
+        self
+    }
+
+    /// None makes every pair of particles interact
+    pub fn set_cutoff(&mut self, cutoff: Option<Real>) -> &mut Self {
+        if let Some(cutoff) = cutoff {
+            if cutoff <= 0.0 {
+                panic!("Cutoff should be greater then 0, use None to disable it");
+            }
+        }
+        self.cutoff = cutoff;
+        self.grid = cutoff.map(SpatialGrid::new);
+        if let Some(grid) = self.grid.as_mut() {
+            grid.rebuild(self.sources.iter().map(|(position, _)| position));
+        }
+        self
+    }
+}
+
+impl Default for Coulomb {
+    fn default() -> Self {
+        Self::new(COULOMB_CONSTANT)
+    }
+}
+
+impl ForceGenerator for Coulomb {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
+        let charge = particle.get_charge();
+        if particle.is_infinite_mass() || charge == 0.0 {
+            return;
+        }
+        let field = self.get_field(&particle.get_position());
+        particle.add_force(&field * charge);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    fn charge(x: Real, charge: Real) -> Particle {
+        let mut particle = Particle::new();
+        particle.set_position(Vec3::from_values(x, 0.0, 0.0));
+        particle.set_charge(charge);
+        particle
+    }
+
+    #[test]
+    fn charges_beyond_the_cutoff_are_ignored() {
+        let particles = [charge(0.0, 1.0), charge(1.0, 1.0), charge(3.0, -1.0)];
+        let mut coulomb = Coulomb::new(1.0);
+        coulomb.set_cutoff(Some(2.0));
+        coulomb.update_sources(&particles);
+
+        let field = coulomb.get_field(&Vec3::new());
+        assert!((field.x + 1.0).abs() < 1.0e-12);
+        assert_eq!(field.y, 0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_cutoff_is_rejected() {
+        Coulomb::new(1.0).set_cutoff(Some(0.0));
+    }
+}
@@ -2,51 +2,214 @@ use crate::particle::force_generator::ForceGenerator;
 use crate::particle::particle_trait::ParticleTrait;
 use crate::types::Real;
 
-struct DeformableSpring<'a, PT: ParticleTrait> {
+/// An elasto-plastic spring. Stretched or compressed beyond the limit of elasticity it
+/// yields, its rest length changes for good and it doesn't come back. Hardening makes
+/// it harder to deform further, and it breaks at the ultimate strain.
+/// The deformation happens when the force is updated, so a spring between two movable
+/// particles should be used by only one of them
+pub struct DeformableSpring<'a, PT: ParticleTrait> {
     other: &'a PT,
     spring_constant: Real,
+    // changes with the plastic deformation
     rest_length: Real,
-    // the maximum length to which the spring_cube could be stretched before deforming and losing elasticity
-    limit_of_elasticity: Real,
-    elasticity_loss_coefficient: Real,
-    elasticity_divider: Real,
+    original_rest_length: Real,
+    // the largest elongation or compression from the rest length which is still elastic,
+    // it grows with hardening
+    yield_elongation: Real,
+    // the ratio of the hardening modulus to the spring constant,
+    // 0 is perfectly plastic
+    hardening: Real,
+    // the spring breaks when stretched by more than this part of its original length
+    ultimate_strain: Option<Real>,
+    is_broken: bool,
 }
 
 impl<'a, PT: ParticleTrait> DeformableSpring<'a, PT> {
+    /// The limit of elasticity is the maximum length to which the spring could be
+    /// stretched before deforming, it should be greater than the rest length
     pub fn new(
         other: &'a PT,
         spring_constant: Real,
         rest_length: Real,
         limit_of_elasticity: Real,
-        max_elasticity_loss_coefficient: Real,
     ) -> Self {
         DeformableSpring {
             other,
             spring_constant,
             rest_length,
-            limit_of_elasticity,
-            elasticity_loss_coefficient: max_elasticity_loss_coefficient,
-            elasticity_divider: 1.0,
+            original_rest_length: rest_length,
+            yield_elongation: (limit_of_elasticity - rest_length).abs(),
+            hardening: 0.0,
+            ultimate_strain: None,
+            is_broken: false,
         }
     }
+
+    pub fn set_hardening(&mut self, hardening: Real) -> &mut Self {
+        self.hardening = hardening;
+        self
+    }
+
+    /// A spring of zero rest length has no strain, it doesn't break
+    pub fn set_ultimate_strain(&mut self, ultimate_strain: Option<Real>) -> &mut Self {
+        self.ultimate_strain = ultimate_strain;
+        self
+    }
+
+    pub fn get_rest_length(&self) -> Real {
+        self.rest_length
+    }
+
+    /// The current length the spring can be stretched to without deforming
+    pub fn get_limit_of_elasticity(&self) -> Real {
+        self.rest_length + self.yield_elongation
+    }
+
+    /// The permanent relative elongation, negative after a compression.
+    /// 0 for a spring of zero rest length, the strain is undefined then
+    pub fn get_plastic_strain(&self) -> Real {
+        if self.original_rest_length == 0.0 {
+            return 0.0;
+        }
+        (self.rest_length - self.original_rest_length) / self.original_rest_length
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.is_broken
+    }
+
+    /// Deforms the spring to the length if it's beyond the limit of elasticity
+    /// and returns the force magnitude, positive when stretched
+    pub fn update_length(&mut self, length: Real) -> Real {
+        if self.is_broken {
+            return 0.0;
+        }
+        if let Some(ultimate_strain) = self.ultimate_strain {
+            let strain = (length - self.original_rest_length) / self.original_rest_length;
+            if self.original_rest_length > 0.0 && strain > ultimate_strain {
+                self.is_broken = true;
+                return 0.0;
+            }
+        }
+        let elongation = length - self.rest_length;
+        let overflow = elongation.abs() - self.yield_elongation;
+        if overflow > 0.0 {
+            // the spring flows until its force is back on the, now harder, yield limit
+            let plastic_flow = overflow / (1.0 + self.hardening);
+            self.rest_length += plastic_flow * elongation.signum();
+            self.yield_elongation += self.hardening * plastic_flow;
+        }
+        self.spring_constant * (length - self.rest_length)
+    }
 }
 
 impl<'a, PT: ParticleTrait> ForceGenerator for DeformableSpring<'a, PT> {
     fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
         // calculate the vector of the spring_cube
         let mut force = particle.get_position() - self.other.get_position();
-        let spring_length = force.magnitude();
-        if spring_length > self.limit_of_elasticity {
-            self.elasticity_divider = self.elasticity_loss_coefficient
-                * ((spring_length - self.limit_of_elasticity) / spring_length);
-            self.limit_of_elasticity = spring_length;
+        let magnitude = self.update_length(force.magnitude());
+        if magnitude == 0.0 {
+            return;
         }
-        // what is it? I added this bcs it was used in (self.spring_constant / divider)
-        let divider = 1.0;
-        let magnitude =
-            (self.spring_constant / divider) * (force.magnitude() - self.rest_length).abs();
         // calculate the final force and apply it
         force.normalize();
         particle.add_force(&force * -magnitude);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    // rest length 1, stretched beyond 1.5 or compressed below 0.5 it yields
+    fn spring(other: &Particle) -> DeformableSpring<'_, Particle> {
+        DeformableSpring::new(other, 10.0, 1.0, 1.5)
+    }
+
+    #[test]
+    fn elastic_below_yield() {
+        let other = Particle::new();
+        let mut spring = spring(&other);
+        assert!((spring.update_length(1.4) - 10.0 * 0.4).abs() < 1.0e-12);
+        assert!((spring.update_length(0.6) - 10.0 * -0.4).abs() < 1.0e-12);
+        // unloaded it's back at rest, nothing changed for good
+        assert_eq!(spring.update_length(1.0), 0.0);
+        assert_eq!(spring.get_rest_length(), 1.0);
+        assert_eq!(spring.get_plastic_strain(), 0.0);
+    }
+
+    #[test]
+    fn permanent_offset_after_yield() {
+        let other = Particle::new();
+        for &hardening in [0.0, 1.0, 3.0].iter() {
+            let mut spring = spring(&other);
+            spring.set_hardening(hardening);
+            // 0.5 beyond the limit of elasticity
+            spring.update_length(2.0);
+            let offset = 0.5 / (1.0 + hardening);
+            assert!((spring.get_rest_length() - (1.0 + offset)).abs() < 1.0e-12);
+            assert!((spring.get_plastic_strain() - offset).abs() < 1.0e-12);
+            // unloading doesn't take it back
+            spring.update_length(1.0);
+            assert!((spring.get_rest_length() - (1.0 + offset)).abs() < 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn yield_grows_with_hardening() {
+        let other = Particle::new();
+        let mut perfectly_plastic = spring(&other);
+        perfectly_plastic.update_length(2.0);
+        // it yields at the same elongation from the new rest length
+        assert!((perfectly_plastic.get_limit_of_elasticity() - 2.0).abs() < 1.0e-12);
+
+        let mut hardening = spring(&other);
+        hardening.set_hardening(1.0);
+        hardening.update_length(2.0);
+        // rest length 1.25, the elastic range grew from 0.5 to 0.75
+        assert!((hardening.get_limit_of_elasticity() - 2.0).abs() < 1.0e-12);
+        let rest_length = hardening.get_rest_length();
+        hardening.update_length(1.95);
+        assert_eq!(hardening.get_rest_length(), rest_length);
+        // the force at the yield limit is higher than before
+        assert!(hardening.update_length(2.0) > 10.0 * 0.5);
+    }
+
+    #[test]
+    fn compression_shortens_the_spring() {
+        let other = Particle::new();
+        let mut spring = spring(&other);
+        // 0.3 beyond the limit of compression
+        let force = spring.update_length(0.2);
+        assert!((spring.get_rest_length() - 0.7).abs() < 1.0e-12);
+        assert!((spring.get_plastic_strain() + 0.3).abs() < 1.0e-12);
+        // it pushes with the force at the yield limit
+        assert!((force - 10.0 * -0.5).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn fracture_at_ultimate_strain() {
+        let other = Particle::new();
+        let mut spring = spring(&other);
+        spring.set_ultimate_strain(Some(0.8));
+        spring.update_length(1.7);
+        assert!(!spring.is_broken());
+        assert_eq!(spring.update_length(1.9), 0.0);
+        assert!(spring.is_broken());
+        // a broken spring stays broken
+        assert_eq!(spring.update_length(1.0), 0.0);
+        assert!(spring.is_broken());
+    }
+
+    #[test]
+    fn zero_rest_length() {
+        let other = Particle::new();
+        let mut spring = DeformableSpring::new(&other, 10.0, 0.0, 0.5);
+        spring.set_ultimate_strain(Some(0.8));
+        spring.update_length(1.0);
+        assert!(!spring.is_broken());
+        assert!((spring.get_rest_length() - 0.5).abs() < 1.0e-12);
+        assert_eq!(spring.get_plastic_strain(), 0.0);
+    }
+}
@@ -1,8 +1,19 @@
 use crate::particle::force_generator::ForceGenerator;
 use crate::particle::particle_trait::ParticleTrait;
 use crate::types::Real;
+use crate::vector::Vec3;
+use std::f64::consts::PI;
 
-struct Drag {
+/// Sea level air at 15 degrees Celsius, in kg / m^3
+pub const AIR_DENSITY: Real = 1.225;
+/// Dynamic viscosity of the same air, in Pa s
+pub const AIR_VISCOSITY: Real = 1.81e-5;
+/// Fresh water at 20 degrees Celsius, in kg / m^3
+pub const WATER_DENSITY: Real = 998.2;
+/// Dynamic viscosity of the same water, in Pa s
+pub const WATER_VISCOSITY: Real = 1.002e-3;
+
+pub struct Drag {
     // velocity drag coefficient
     k1: Real,
     // velocity squared drag coefficient
@@ -13,16 +24,118 @@ impl Drag {
     pub fn new(k1: Real, k2: Real) -> Self {
         Self { k1, k2 }
     }
+
+    /// Pressure drag 1/2 * density * drag coefficient * area * v^2. It dominates for
+    /// Reynolds numbers above about 1000, i.e. for everything bigger than a raindrop
+    /// moving in air. The drag coefficient is about 0.47 for a sphere, 1.05 for a cube
+    /// and 1.28 for a flat plate facing the flow
+    pub fn from_physical(fluid_density: Real, drag_coefficient: Real, area: Real) -> Self {
+        Self::new(0.0, 0.5 * fluid_density * drag_coefficient * area)
+    }
+
+    /// Viscous (Stokes) drag of a sphere 3 pi * viscosity * diameter * v, valid for
+    /// Reynolds numbers below 1: dust, droplets of fog, particles sinking in oil
+    pub fn from_stokes(dynamic_viscosity: Real, diameter: Real) -> Self {
+        Self::new(3.0 * PI as Real * dynamic_viscosity * diameter, 0.0)
+    }
+
+    /// Sphere drag for any Reynolds number up to about 2e5. Both terms are used,
+    /// which gives the drag coefficient 24 / Re + 0.4
+    pub fn for_sphere(fluid_density: Real, dynamic_viscosity: Real, diameter: Real) -> Self {
+        let area = PI as Real * diameter * diameter / 4.0;
+        Self::new(
+            3.0 * PI as Real * dynamic_viscosity * diameter,
+            0.2 * fluid_density * area,
+        )
+    }
+
+    /// The speed at which the drag balances the weight of a falling body.
+    /// Buoyancy is not taken into account, infinity if there is no drag
+    pub fn terminal_velocity(&self, mass: Real, gravity: Real) -> Real {
+        let weight = mass * gravity.abs();
+        if self.k2 > 0.0 {
+            // k2 v^2 + k1 v - weight = 0
+            (-self.k1 + (self.k1 * self.k1 + 4.0 * self.k2 * weight).sqrt()) / (2.0 * self.k2)
+        } else if self.k1 > 0.0 {
+            weight / self.k1
+        } else {
+            Real::INFINITY
+        }
+    }
+
+    pub fn get_k1(&self) -> Real {
+        self.k1
+    }
+
+    pub fn get_k2(&self) -> Real {
+        self.k2
+    }
+}
+
+/// The ratio of inertial to viscous forces, tells which drag law applies.
+/// The length is the size of the body across the flow, e.g. a diameter
+pub fn reynolds_number(
+    fluid_density: Real,
+    dynamic_viscosity: Real,
+    speed: Real,
+    length: Real,
+) -> Real {
+    fluid_density * speed * length / dynamic_viscosity
 }
 
 impl ForceGenerator for Drag {
     fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
-        let mut force = particle.get_velocity();
-        let magnitude = force.magnitude();
-        let drag_coefficient = self.k1 * magnitude + self.k2 * magnitude * magnitude;
+        particle.add_force(drag_force(&particle.get_velocity(), self.k1, self.k2));
+    }
+}
+
+/// Force against the velocity relative to the medium, k1 * |v| + k2 * |v|^2 in magnitude
+pub(crate) fn drag_force(relative_velocity: &Vec3, k1: Real, k2: Real) -> Vec3 {
+    let mut force = *relative_velocity;
+    let magnitude = force.magnitude();
+    let drag_coefficient = k1 * magnitude + k2 * magnitude * magnitude;
+
+    force.normalize();
+    force *= -drag_coefficient;
+    force
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    #[test]
+    fn drag_balances_the_weight_at_the_terminal_velocity() {
+        let drags = [
+            Drag::new(0.3, 0.0),
+            Drag::from_physical(AIR_DENSITY, 0.47, 0.01),
+            Drag::for_sphere(AIR_DENSITY, AIR_VISCOSITY, 0.1),
+        ];
+        for drag in drags.iter() {
+            let speed = drag.terminal_velocity(2.0, -9.81);
+            let velocity = Vec3::from_values(0.0, -speed, 0.0);
+            let force = drag_force(&velocity, drag.get_k1(), drag.get_k2());
+            assert!((force.y - 2.0 * 9.81).abs() < 1.0e-9);
+        }
+        assert_eq!(
+            Drag::new(0.0, 0.0).terminal_velocity(1.0, 9.81),
+            Real::INFINITY
+        );
+    }
 
-        force.normalize();
-        force *= -drag_coefficient;
-        particle.add_force(force);
+    #[test]
+    fn falling_body_reaches_the_terminal_velocity() {
+        let mut drag = Drag::for_sphere(AIR_DENSITY, AIR_VISCOSITY, 0.1);
+        let mut particle = Particle::new();
+        particle.set_mass(0.5);
+        particle.set_damping(1.0);
+        particle.add_acceleration(Vec3::from_values(0.0, -9.81, 0.0));
+        for _ in 0..20000 {
+            drag.update_force(&mut particle, 0.001);
+            particle.integrate(0.001);
+        }
+        let speed = drag.terminal_velocity(0.5, -9.81);
+        assert!((particle.get_velocity().magnitude() - speed).abs() < 1.0e-3 * speed);
     }
 }
@@ -0,0 +1,30 @@
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// Uniform electric field, pushes charged particles with the force q * E
+pub struct ElectricField {
+    // in volts per meter
+    field: Vec3,
+}
+
+impl ElectricField {
+    pub fn new(field: Vec3) -> ElectricField {
+        ElectricField { field }
+    }
+
+    pub fn set_field(&mut self, field: Vec3) -> &mut Self {
+        self.field = field;
+        self
+    }
+}
+
+impl ForceGenerator for ElectricField {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
+        if particle.is_infinite_mass() {
+            return;
+        }
+        particle.add_force(&self.field * particle.get_charge());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// A stiff damped spring to an anchor which stays stable with long steps. The motion of
+/// a damped harmonic oscillator is solved analytically over the step and the force that
+/// moves the particle to the predicted position is applied, see Millington, "Game
+/// Physics Engine Development". The particle gets the average velocity of the step, so
+/// long steps lose some energy instead of exploding. integrate moves the particle with
+/// the old velocity first, so the new one carries it to the position predicted from
+/// there during the next step. Particles moved with integrate_symplectic reach the
+/// predicted position in the same step, see set_symplectic. Forces on the particle
+/// should be added before this one, they are taken into account then. Good for camera
+/// springs and tethers
+pub struct FakeSpring {
+    anchor: Vec3,
+    // the anchor is moved by it during the step
+    anchor_velocity: Vec3,
+    spring_constant: Real,
+    damping: Real,
+    // the equilibrium is this far from the anchor, along the spring
+    rest_length: Real,
+    // the particle is moved with integrate_symplectic instead of integrate
+    symplectic: bool,
+}
+
+impl FakeSpring {
+    pub fn new(anchor: Vec3, spring_constant: Real, damping: Real) -> Self {
+        FakeSpring {
+            anchor,
+            anchor_velocity: Vec3::new(),
+            spring_constant,
+            damping,
+            rest_length: 0.0,
+            symplectic: false,
+        }
+    }
+
+    pub fn set_anchor(&mut self, anchor: Vec3) -> &mut Self {
+        self.anchor = anchor;
+        self
+    }
+
+    /// A moving anchor, e.g. an object the camera follows
+    pub fn set_anchor_velocity(&mut self, anchor_velocity: Vec3) -> &mut Self {
+        self.anchor_velocity = anchor_velocity;
+        self
+    }
+
+    pub fn set_spring_constant(&mut self, spring_constant: Real) -> &mut Self {
+        self.spring_constant = spring_constant;
+        self
+    }
+
+    pub fn set_damping(&mut self, damping: Real) -> &mut Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn set_rest_length(&mut self, rest_length: Real) -> &mut Self {
+        self.rest_length = rest_length;
+        self
+    }
+
+    pub fn set_symplectic(&mut self, symplectic: bool) -> &mut Self {
+        self.symplectic = symplectic;
+        self
+    }
+
+    /// The position of the particle after the duration, relative to the anchor
+    fn predict(&self, offset: Vec3, velocity: Vec3, mass: Real, duration: Real) -> Vec3 {
+        // the equilibrium is taken as fixed for the step
+        let mut equilibrium = offset;
+        equilibrium.normalize();
+        equilibrium *= self.rest_length;
+        let x = offset - equilibrium;
+
+        let decay = 0.5 * self.damping / mass;
+        let natural_frequency_squared = self.spring_constant / mass;
+        let discriminant = decay * decay - natural_frequency_squared;
+        let mut initial_rate = velocity;
+        initial_rate.add_scaled(&x, decay);
+
+        let target = if discriminant < 0.0 {
+            // underdamped, the particle oscillates around the equilibrium
+            let frequency = (-discriminant).sqrt();
+            let (sin, cos) = (frequency * duration).sin_cos();
+            let mut target = &x * cos;
+            target.add_scaled(&initial_rate, sin / frequency);
+            &target * (-decay * duration).exp()
+        } else if discriminant == 0.0 {
+            // critically damped, the fastest return without overshooting
+            let mut target = x;
+            target.add_scaled(&initial_rate, duration);
+            &target * (-decay * duration).exp()
+        } else {
+            // overdamped, the particle creeps back
+            let root = discriminant.sqrt();
+            let (fast, slow) = (-decay - root, -decay + root);
+            let mut slow_part = velocity;
+            slow_part.add_scaled(&x, -fast);
+            slow_part *= 1.0 / (slow - fast);
+            let fast_part = x - slow_part;
+            let mut target = &slow_part * (slow * duration).exp();
+            target.add_scaled(&fast_part, (fast * duration).exp());
+            target
+        };
+        target + equilibrium
+    }
+}
+
+impl ForceGenerator for FakeSpring {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, duration: Real) {
+        if particle.is_infinite_mass() || duration <= 0.0 {
+            return;
+        }
+        let damping = particle.get_damping().powf(duration);
+        if damping <= 0.0 {
+            return;
+        }
+        let mass = particle.get_mass();
+        let position = particle.get_position();
+        let velocity = particle.get_velocity();
+        // the acceleration of the particle and forces added before, like gravity,
+        // are constant during the step, they move the equilibrium of the spring
+        let mut external = particle.get_acceleration();
+        external.add_scaled(&particle.get_force_accum(), particle.get_inverse_mass());
+        let mut anchor = self.anchor;
+        if self.spring_constant > 0.0 {
+            anchor.add_scaled(&external, mass / self.spring_constant);
+        }
+        // the new velocity moves the particle from here, integrate has already moved it
+        // with the old velocity and the anchor has moved as well by then
+        let mut start = position;
+        if !self.symplectic {
+            start.add_scaled(&velocity, duration);
+            anchor.add_scaled(&self.anchor_velocity, duration);
+        }
+        let offset = start - anchor;
+        let relative_velocity = velocity - self.anchor_velocity;
+
+        let mut target = anchor + self.predict(offset, relative_velocity, mass, duration);
+        target.add_scaled(&self.anchor_velocity, duration);
+
+        // the velocity becomes (velocity + acceleration * duration) * damping
+        let mut acceleration = &(target - start) * (1.0 / (duration * duration * damping));
+        acceleration.add_scaled(&velocity, -1.0 / duration);
+        acceleration -= external;
+        particle.add_force(&acceleration * mass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::force_generator::anchored_spring::AnchoredSpring;
+    use crate::particle::force_generator::test_helpers;
+    use crate::particle::Particle;
+
+    fn particle() -> Particle {
+        test_helpers::particle(Vec3::from_values(1.0, 0.0, 0.0), Vec3::new())
+    }
+
+    #[test]
+    fn stiff_spring_is_stable_with_long_steps() {
+        // the natural frequency is 1000 rad / s, steps are a hundred radians long
+        let mut spring = FakeSpring::new(Vec3::new(), 1.0e6, 0.0);
+        spring.set_symplectic(true);
+        let mut particle = particle();
+        for _ in 0..100 {
+            spring.update_force(&mut particle, 0.1);
+            particle.integrate_symplectic(0.1);
+            assert!(particle.get_position().magnitude() <= 1.0 + 1.0e-9);
+        }
+
+        // an ordinary spring explodes
+        let anchor = Vec3::new();
+        let mut explicit = AnchoredSpring::new(&anchor, 1.0e6, 0.0);
+        let mut particle = self::particle();
+        for _ in 0..10 {
+            explicit.update_force(&mut particle, 0.1);
+            particle.integrate_symplectic(0.1);
+        }
+        assert!(particle.get_position().magnitude() > 1.0e3);
+    }
+
+    #[test]
+    fn damped_spring_settles_under_gravity() {
+        let mut spring = FakeSpring::new(Vec3::new(), 1.0e6, 500.0);
+        spring.set_symplectic(true);
+        let mut particle = particle();
+        particle.add_acceleration(Vec3::from_values(0.0, -10.0, 0.0));
+        for _ in 0..100 {
+            spring.update_force(&mut particle, 0.1);
+            particle.integrate_symplectic(0.1);
+        }
+        let sag = Vec3::from_values(0.0, -1.0e-5, 0.0);
+        assert!((particle.get_position() - sag).magnitude() < 1.0e-9);
+    }
+
+    #[test]
+    fn critically_damped_spring_settles_with_integrate() {
+        let mut spring = FakeSpring::new(Vec3::new(), 1.0e6, 2000.0);
+        let mut particle = particle();
+        for _ in 0..10 {
+            spring.update_force(&mut particle, 0.1);
+            particle.integrate(0.1);
+        }
+        assert!(particle.get_position().magnitude() < 1.0e-9);
+        assert!(particle.get_velocity().magnitude() < 1.0e-9);
+    }
+
+    #[test]
+    fn stiff_spring_is_stable_with_integrate() {
+        let mut spring = FakeSpring::new(Vec3::new(), 1.0e6, 0.0);
+        let mut particle = particle();
+        for _ in 0..1000 {
+            spring.update_force(&mut particle, 0.1);
+            particle.integrate(0.1);
+            assert!(particle.get_position().magnitude() <= 1.0 + 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn moving_anchor_is_followed_with_integrate() {
+        // a camera spring behind an object moving with a constant velocity
+        let anchor_velocity = Vec3::from_values(0.0, 0.0, 5.0);
+        let mut spring = FakeSpring::new(Vec3::new(), 100.0, 20.0);
+        spring.set_anchor_velocity(anchor_velocity);
+        let mut particle = particle();
+        for step in 0..300 {
+            spring.set_anchor(&anchor_velocity * (step as Real * 0.05));
+            spring.update_force(&mut particle, 0.05);
+            particle.integrate(0.05);
+        }
+        let anchor = &anchor_velocity * 15.0;
+        assert!((particle.get_position() - anchor).magnitude() < 1.0e-3);
+        assert!((particle.get_velocity() - anchor_velocity).magnitude() < 1.0e-3);
+    }
+}
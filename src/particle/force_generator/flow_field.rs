@@ -0,0 +1,335 @@
+use crate::noise::PerlinNoise;
+use crate::particle::force_generator::drag::drag_force;
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::types::Real;
+use crate::vector::Vec3;
+use std::f64::consts::PI;
+
+/// Velocity of a moving medium (air, water) sampled at any point
+pub trait FlowField {
+    fn get_velocity(&self, position: &Vec3) -> Vec3;
+
+    /// Time dependent fields change with the simulation time
+    fn set_time(&mut self, _time: Real) {}
+}
+
+pub struct UniformWind {
+    velocity: Vec3,
+}
+
+impl UniformWind {
+    pub fn new(velocity: Vec3) -> Self {
+        Self { velocity }
+    }
+
+    pub fn set_velocity(&mut self, velocity: Vec3) -> &mut Self {
+        self.velocity = velocity;
+        self
+    }
+}
+
+impl FlowField for UniformWind {
+    fn get_velocity(&self, _position: &Vec3) -> Vec3 {
+        self.velocity
+    }
+}
+
+/// Wind which randomly gets stronger and weaker. Gusts travel downwind with the mean
+/// wind speed, so a row of trees bends one after another
+pub struct GustingWind {
+    velocity: Vec3,
+    // the largest part of the mean speed added or taken by a gust
+    gust_strength: Real,
+    // gusts per second
+    gust_frequency: Real,
+    noise: PerlinNoise,
+    time: Real,
+}
+
+impl GustingWind {
+    pub fn new(velocity: Vec3, gust_strength: Real, gust_frequency: Real) -> Self {
+        Self {
+            velocity,
+            gust_strength,
+            gust_frequency,
+            noise: PerlinNoise::new(0),
+            time: 0.0,
+        }
+    }
+
+    pub fn set_seed(&mut self, seed: u64) -> &mut Self {
+        self.noise = PerlinNoise::new(seed);
+        self
+    }
+}
+
+impl FlowField for GustingWind {
+    fn get_velocity(&self, position: &Vec3) -> Vec3 {
+        let speed = self.velocity.magnitude();
+        if speed == 0.0 {
+            return self.velocity;
+        }
+        // the time at which the air at this position has passed the origin
+        let time = self.time - (position * &self.velocity) / (speed * speed);
+        // perlin noise is zero at integer coordinates, the other coordinates avoid them
+        let gust = self.noise.get(time * self.gust_frequency, 0.31, 0.73);
+        &self.velocity * (1.0 + self.gust_strength * gust)
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.time = time;
+    }
+}
+
+/// Air swirling around an axis, e.g. a tornado or a whirlpool. Lamb-Oseen vortex: the
+/// fluid rotates like a solid body inside the core and slows down as 1/r outside of it
+pub struct Vortex {
+    center: Vec3,
+    axis: Vec3,
+    // the circulation, counterclockwise around the axis when positive
+    strength: Real,
+    core_radius: Real,
+    // speed of the air drawn towards the axis
+    inflow: Real,
+    // speed of the air along the axis inside the core
+    updraft: Real,
+}
+
+impl Vortex {
+    pub fn new(center: Vec3, mut axis: Vec3, strength: Real, core_radius: Real) -> Self {
+        axis.normalize();
+        Self {
+            center,
+            axis,
+            strength,
+            core_radius,
+            inflow: 0.0,
+            updraft: 0.0,
+        }
+    }
+
+    pub fn set_inflow(&mut self, inflow: Real) -> &mut Self {
+        self.inflow = inflow;
+        self
+    }
+
+    pub fn set_updraft(&mut self, updraft: Real) -> &mut Self {
+        self.updraft = updraft;
+        self
+    }
+
+    pub fn set_center(&mut self, center: Vec3) -> &mut Self {
+        self.center = center;
+        self
+    }
+}
+
+impl FlowField for Vortex {
+    fn get_velocity(&self, position: &Vec3) -> Vec3 {
+        let offset = *position - self.center;
+        let radial = offset - &self.axis * (&offset * &self.axis);
+        let r = radial.magnitude();
+        if r == 0.0 {
+            return &self.axis * self.updraft;
+        }
+        let core = 1.0 - (-(r * r) / (self.core_radius * self.core_radius)).exp();
+        let tangential_speed = self.strength / (2.0 * PI as Real * r) * core;
+        let mut velocity = &(self.axis % radial) * (tangential_speed / r);
+        velocity.add_scaled(&radial, -self.inflow * core / r);
+        velocity.add_scaled(&self.axis, self.updraft * (1.0 - core));
+        velocity
+    }
+}
+
+/// Turbulence without sources and sinks, the curl of a noise vector field.
+/// Smoke follows it in swirls instead of gathering at some points.
+/// See Bridson et al., "Curl-Noise for Procedural Fluid Flow"
+pub struct CurlNoise {
+    // the size of the swirls
+    scale: Real,
+    strength: Real,
+    // how fast the swirls change, in scales per second
+    evolution_speed: Real,
+    noise: PerlinNoise,
+    time: Real,
+}
+
+impl CurlNoise {
+    pub fn new(scale: Real, strength: Real) -> Self {
+        Self {
+            scale,
+            strength,
+            evolution_speed: 0.0,
+            noise: PerlinNoise::new(0),
+            time: 0.0,
+        }
+    }
+
+    pub fn set_evolution_speed(&mut self, evolution_speed: Real) -> &mut Self {
+        self.evolution_speed = evolution_speed;
+        self
+    }
+
+    pub fn set_seed(&mut self, seed: u64) -> &mut Self {
+        self.noise = PerlinNoise::new(seed);
+        self
+    }
+
+    /// Three independent noise values, the potential of the flow
+    fn get_potential(&self, x: Real, y: Real, z: Real) -> Vec3 {
+        // moving through the noise along a diagonal keeps the swirls changing in time
+        let shift = self.time * self.evolution_speed;
+        let (x, y, z) = (x + shift, y + shift, z + shift);
+        Vec3::from_values(
+            self.noise.get(x, y, z),
+            self.noise.get(y + 31.4, z + 47.2, x + 12.9),
+            self.noise.get(z - 19.1, x + 83.3, y - 55.7),
+        )
+    }
+}
+
+impl FlowField for CurlNoise {
+    fn get_velocity(&self, position: &Vec3) -> Vec3 {
+        let (x, y, z) = (
+            position.x / self.scale,
+            position.y / self.scale,
+            position.z / self.scale,
+        );
+        let e = 1.0e-4;
+        let dx = self.get_potential(x + e, y, z) - self.get_potential(x - e, y, z);
+        let dy = self.get_potential(x, y + e, z) - self.get_potential(x, y - e, z);
+        let dz = self.get_potential(x, y, z + e) - self.get_potential(x, y, z - e);
+        let curl = Vec3::from_values(dy.z - dz.y, dz.x - dx.z, dx.y - dy.x);
+        &curl * (self.strength / (2.0 * e))
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.time = time;
+    }
+}
+
+/// Drag against the medium moving with the flow field, not against still air like Drag
+pub struct FlowDrag<F: FlowField> {
+    field: F,
+    // velocity drag coefficient
+    k1: Real,
+    // velocity squared drag coefficient
+    k2: Real,
+}
+
+impl<F: FlowField> FlowDrag<F> {
+    pub fn new(field: F, k1: Real, k2: Real) -> Self {
+        Self { field, k1, k2 }
+    }
+
+    pub fn get_field(&self) -> &F {
+        &self.field
+    }
+
+    pub fn get_field_mut(&mut self) -> &mut F {
+        &mut self.field
+    }
+}
+
+impl<F: FlowField> ForceGenerator for FlowDrag<F> {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
+        let position = particle.get_position();
+        let relative_velocity = particle.get_velocity() - self.field.get_velocity(&position);
+        particle.add_force(drag_force(&relative_velocity, self.k1, self.k2));
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.field.set_time(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vortex() -> Vortex {
+        Vortex::new(Vec3::new(), Vec3::from_values(0.0, 0.0, 2.0), 10.0, 0.5)
+    }
+
+    #[test]
+    fn vortex_slows_down_as_inverse_distance_outside_the_core() {
+        let velocity = vortex().get_velocity(&Vec3::from_values(5.0, 0.0, 3.0));
+        // counterclockwise around z
+        let expected = 10.0 / (2.0 * PI as Real * 5.0);
+        assert!((velocity.y - expected).abs() < 1.0e-9);
+        assert!(velocity.x.abs() < 1.0e-12 && velocity.z.abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn vortex_rotates_like_a_solid_body_inside_the_core() {
+        let vortex = vortex();
+        let near = vortex.get_velocity(&Vec3::from_values(0.0, 0.01, 0.0));
+        let far = vortex.get_velocity(&Vec3::from_values(0.0, 0.02, 0.0));
+        let angular_velocity = 10.0 / (2.0 * PI as Real * 0.5 * 0.5);
+        assert!((near.x + angular_velocity * 0.01).abs() < 1.0e-3 * angular_velocity * 0.01);
+        assert!((far.x / near.x - 2.0).abs() < 1.0e-2);
+    }
+
+    #[test]
+    fn vortex_draws_air_in_and_up() {
+        let mut vortex = vortex();
+        vortex.set_inflow(1.0).set_updraft(2.0);
+        let velocity = vortex.get_velocity(&Vec3::from_values(5.0, 0.0, 0.0));
+        assert!((velocity.x + 1.0).abs() < 1.0e-9);
+        assert!(vortex.get_velocity(&Vec3::new()).z == 2.0);
+    }
+
+    #[test]
+    fn gusts_change_in_time_within_their_strength() {
+        let mut wind = GustingWind::new(Vec3::from_values(10.0, 0.0, 0.0), 0.4, 0.5);
+        let position = Vec3::from_values(3.0, 1.0, 0.0);
+        let mut speeds = Vec::new();
+        for step in 0..200 {
+            wind.set_time(step as Real * 0.1);
+            let velocity = wind.get_velocity(&position);
+            // gusts change the speed, not the direction
+            assert!(velocity.y == 0.0 && velocity.z == 0.0);
+            assert!(velocity.x >= 10.0 * (1.0 - 0.4) && velocity.x <= 10.0 * (1.0 + 0.4));
+            speeds.push(velocity.x);
+        }
+        let slowest = speeds.iter().cloned().fold(Real::MAX, Real::min);
+        let fastest = speeds.iter().cloned().fold(Real::MIN, Real::max);
+        assert!(fastest - slowest > 1.0, "{} {}", slowest, fastest);
+
+        // the same gust reaches a point downwind later
+        wind.set_time(5.0);
+        let upwind = wind.get_velocity(&Vec3::new());
+        wind.set_time(5.3);
+        let downwind = wind.get_velocity(&Vec3::from_values(3.0, 0.0, 0.0));
+        assert!((upwind.x - downwind.x).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn curl_noise_is_divergence_free() {
+        let mut noise = CurlNoise::new(2.0, 3.0);
+        noise.set_evolution_speed(0.5).set_time(1.7);
+        let h = 1.0e-3;
+        for i in 0..20 {
+            let i = i as Real;
+            let point = Vec3::from_values(i * 0.37, 1.3 - i * 0.21, i * i * 0.05);
+            let derivative = |axis: Vec3| {
+                let difference = noise.get_velocity(&(point + &axis * h))
+                    - noise.get_velocity(&(point - &axis * h));
+                &difference * (1.0 / (2.0 * h))
+            };
+            let dx = derivative(Vec3::from_values(1.0, 0.0, 0.0));
+            let dy = derivative(Vec3::from_values(0.0, 1.0, 0.0));
+            let dz = derivative(Vec3::from_values(0.0, 0.0, 1.0));
+            let divergence = dx.x + dy.y + dz.z;
+            // compared to how fast the flow changes at all
+            let gradient = dx.magnitude() + dy.magnitude() + dz.magnitude();
+            assert!(
+                divergence.abs() < 1.0e-3 * gradient,
+                "{} {}",
+                divergence,
+                gradient
+            );
+        }
+    }
+}
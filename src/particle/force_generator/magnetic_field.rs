@@ -0,0 +1,111 @@
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// Uniform magnetic field, deflects moving charged particles with the Lorentz force q v x B
+pub struct MagneticField {
+    // in teslas
+    field: Vec3,
+}
+
+impl MagneticField {
+    pub fn new(field: Vec3) -> MagneticField {
+        MagneticField { field }
+    }
+
+    pub fn set_field(&mut self, field: Vec3) -> &mut Self {
+        self.field = field;
+        self
+    }
+}
+
+impl ForceGenerator for MagneticField {
+    /// The magnetic force only turns the velocity. Applied as is, every step would make
+    /// the particle a bit faster and it would spiral out, so the force is chosen to turn
+    /// the velocity by the exact angle of the step
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, duration: Real) {
+        let charge = particle.get_charge();
+        let strength = self.field.magnitude();
+        if particle.is_infinite_mass() || charge == 0.0 || strength == 0.0 {
+            return;
+        }
+        let velocity = particle.get_velocity();
+        if duration <= 0.0 {
+            particle.add_force(&(velocity % self.field) * charge);
+            return;
+        }
+
+        // the velocity rotates around the field with the cyclotron angular frequency
+        let axis = &self.field * (1.0 / strength);
+        let angle = -charge * strength * particle.get_inverse_mass() * duration;
+        let (sin, cos) = angle.sin_cos();
+        let mut rotated = &velocity * cos;
+        rotated.add_scaled(&(axis % velocity), sin);
+        rotated.add_scaled(&axis, (&axis * &velocity) * (1.0 - cos));
+        particle.add_force(&(rotated - velocity) * (particle.get_mass() / duration));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+    use std::f64::consts::PI;
+
+    // a particle of mass 2 and charge 0.5 moving at 3 m/s across a 4 T field
+    fn gyrating_particle() -> (Particle, MagneticField) {
+        let mut particle = Particle::new();
+        particle.set_mass(2.0).set_damping(1.0).set_charge(0.5);
+        particle.set_velocity(Vec3::from_values(3.0, 0.0, 0.0));
+        (
+            particle,
+            MagneticField::new(Vec3::from_values(0.0, 0.0, 4.0)),
+        )
+    }
+
+    fn step(particle: &mut Particle, field: &mut MagneticField, duration: Real) {
+        field.update_force(particle, duration);
+        particle.integrate_symplectic(duration);
+    }
+
+    #[test]
+    fn cyclotron_radius() {
+        let (mut particle, mut field) = gyrating_particle();
+        // r = m * v / (|q| * B)
+        let radius = 2.0 * 3.0 / (0.5 * 4.0);
+        // q * v x B points to -y, the circle is centered there
+        let center = Vec3::from_values(0.0, -radius, 0.0);
+        let steps = 10_000;
+        let duration = 2.0 * PI as Real * 2.0 / (0.5 * 4.0) / steps as Real;
+        for _ in 0..steps {
+            step(&mut particle, &mut field, duration);
+            let distance = (particle.get_position() - center).magnitude();
+            assert!((distance - radius).abs() < 1.0e-3 * radius, "{}", distance);
+            assert!((particle.get_velocity().magnitude() - 3.0).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn cyclotron_period() {
+        let (mut particle, mut field) = gyrating_particle();
+        // T = 2 * pi * m / (|q| * B)
+        let period = 2.0 * PI as Real * 2.0 / (0.5 * 4.0);
+        let steps = 1000;
+        for _ in 0..steps {
+            step(&mut particle, &mut field, period / steps as Real);
+        }
+        // one full turn, the velocity points along x again
+        let velocity = particle.get_velocity();
+        assert!((velocity - Vec3::from_values(3.0, 0.0, 0.0)).magnitude() < 1.0e-9);
+        assert!(particle.get_position().magnitude() < 1.0e-9);
+
+        // half a turn later it moves the other way, a diameter away
+        for _ in 0..steps / 2 {
+            step(&mut particle, &mut field, period / steps as Real);
+        }
+        let opposite = Vec3::from_values(0.0, -6.0, 0.0);
+        assert!((particle.get_position() - opposite).magnitude() < 0.02);
+        assert!((particle.get_velocity() + Vec3::from_values(3.0, 0.0, 0.0)).magnitude() < 1.0e-9);
+    }
+}
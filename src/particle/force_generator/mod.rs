@@ -2,16 +2,31 @@
 
 pub mod air_buoyancy;
 pub mod anchored_spring;
+pub mod angle_spring;
+pub mod atmospheric_drag;
 pub mod bangee;
 pub mod buoyancy;
+pub mod coulomb;
 pub mod deformable_spring;
 pub mod drag;
+pub mod electric_field;
+pub mod fake_spring;
+pub mod flow_field;
 pub mod gravity;
+pub mod magnetic_field;
+pub mod n_body_gravity;
+pub mod point_gravity;
 pub mod spring;
+#[cfg(test)]
+mod test_helpers;
 
 use crate::particle::particle_trait::ParticleTrait;
 use crate::types::Real;
 
 pub trait ForceGenerator {
     fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, duration: Real);
+
+    /// Tells time dependent generators the simulation time, should be called
+    /// once a step before forces are updated
+    fn set_time(&mut self, _time: Real) {}
 }
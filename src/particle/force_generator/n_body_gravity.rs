@@ -0,0 +1,401 @@
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// In m^3 / (kg s^2)
+pub const GRAVITATIONAL_CONSTANT: Real = 6.674e-11;
+// nodes this deep aren't split anymore, bodies closer than their size share a leaf
+const MAX_TREE_DEPTH: u32 = 32;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NBodyMode {
+    // every pair of bodies interacts, O(n^2)
+    Exact,
+    // far groups of bodies act as a single body at their center of mass, O(n log n)
+    BarnesHut,
+}
+
+struct OctreeNode {
+    center: Vec3,
+    half_size: Real,
+    mass: Real,
+    center_of_mass: Vec3,
+    // children are stored next to each other, the index of the first one
+    first_child: Option<usize>,
+    // a leaf holds one body, unless it's at the maximal depth
+    bodies: Vec<usize>,
+}
+
+impl OctreeNode {
+    fn new(center: Vec3, half_size: Real) -> Self {
+        Self {
+            center,
+            half_size,
+            mass: 0.0,
+            center_of_mass: Vec3::new(),
+            first_child: None,
+            bodies: Vec::new(),
+        }
+    }
+
+    fn get_octant(&self, position: &Vec3) -> usize {
+        let mut octant = 0;
+        if position.x >= self.center.x {
+            octant |= 1;
+        }
+        if position.y >= self.center.y {
+            octant |= 2;
+        }
+        if position.z >= self.center.z {
+            octant |= 4;
+        }
+        octant
+    }
+
+    fn contains(&self, position: &Vec3) -> bool {
+        (position.x - self.center.x).abs() <= self.half_size
+            && (position.y - self.center.y).abs() <= self.half_size
+            && (position.z - self.center.z).abs() <= self.half_size
+    }
+}
+
+/// Mutual gravitational attraction of particles. Positions and masses of the bodies are
+/// captured by update_sources, then update_force pulls any particle towards all of them.
+/// Particles with infinite mass don't attract, they can't be a part of the sum
+pub struct NBodyGravity {
+    gravitational_constant: Real,
+    // added to the distance as sqrt(r^2 + e^2), so close encounters don't give huge forces
+    softening: Real,
+    mode: NBodyMode,
+    // a node of this size divided by the distance to it is treated as a single body
+    opening_angle: Real,
+    sources: Vec<(Vec3, Real)>,
+    nodes: Vec<OctreeNode>,
+}
+
+impl NBodyGravity {
+    pub fn new(gravitational_constant: Real) -> Self {
+        Self {
+            gravitational_constant,
+            softening: 0.0,
+            mode: NBodyMode::Exact,
+            opening_angle: 0.5,
+            sources: Vec::new(),
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Should be called every step before forces are updated
+    pub fn update_sources<P: ParticleTrait>(&mut self, particles: &[P]) {
+        self.sources.clear();
+        for particle in particles.iter() {
+            if particle.is_infinite_mass() {
+                continue;
+            }
+            self.sources
+                .push((particle.get_position(), particle.get_mass()));
+        }
+        self.nodes.clear();
+        if self.mode == NBodyMode::BarnesHut {
+            self.build_tree();
+        }
+    }
+
+    /// Updates sources and adds gravity of all particles to each of them
+    pub fn apply_to_all<P: ParticleTrait>(&mut self, particles: &mut [P], duration: Real) {
+        self.update_sources(particles);
+        for particle in particles.iter_mut() {
+            self.update_force(particle, duration);
+        }
+    }
+
+    /// Gravitational acceleration at the position
+    pub fn get_field(&self, position: &Vec3) -> Vec3 {
+        match self.mode {
+            NBodyMode::Exact => {
+                let mut field = Vec3::new();
+                for (source, mass) in self.sources.iter() {
+                    field += self.attraction(position, source, *mass);
+                }
+                field
+            }
+            NBodyMode::BarnesHut => self.get_tree_field(position),
+        }
+    }
+
+    fn get_tree_field(&self, position: &Vec3) -> Vec3 {
+        let mut field = Vec3::new();
+        if self.nodes.is_empty() {
+            return field;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.mass == 0.0 {
+                continue;
+            }
+            let first_child = match node.first_child {
+                Some(first_child) => first_child,
+                None => {
+                    // bodies of a leaf are summed exactly, the attracted body itself
+                    // is at zero distance and doesn't contribute
+                    for &body in node.bodies.iter() {
+                        let (source, mass) = self.sources[body];
+                        field += self.attraction(position, &source, mass);
+                    }
+                    continue;
+                }
+            };
+            let distance = (node.center_of_mass - *position).magnitude();
+            // a node around the position would include the attracted body itself
+            if !node.contains(position) && 2.0 * node.half_size < self.opening_angle * distance {
+                field += self.attraction(position, &node.center_of_mass, node.mass);
+            } else {
+                stack.extend(first_child..first_child + 8);
+            }
+        }
+        field
+    }
+
+    fn attraction(&self, position: &Vec3, source: &Vec3, mass: Real) -> Vec3 {
+        let offset = *source - *position;
+        let square_distance = offset.square_magnitude() + self.softening * self.softening;
+        // the body itself
+        if square_distance == 0.0 {
+            return Vec3::new();
+        }
+        &offset * (self.gravitational_constant * mass / (square_distance * square_distance.sqrt()))
+    }
+
+    fn build_tree(&mut self) {
+        if self.sources.is_empty() {
+            return;
+        }
+        let mut min = self.sources[0].0;
+        let mut max = min;
+        for (position, _) in self.sources.iter() {
+            min.x = min.x.min(position.x);
+            min.y = min.y.min(position.y);
+            min.z = min.z.min(position.z);
+            max.x = max.x.max(position.x);
+            max.y = max.y.max(position.y);
+            max.z = max.z.max(position.z);
+        }
+        let center = &(min + max) * 0.5;
+        let half_size = (max.x - min.x).max(max.y - min.y).max(max.z - min.z) * 0.5;
+        // a bit larger, so the bodies on the border are inside
+        self.nodes
+            .push(OctreeNode::new(center, half_size * 1.001 + Real::EPSILON));
+        for index in 0..self.sources.len() {
+            self.insert(index);
+        }
+        for node in self.nodes.iter_mut() {
+            if let [body] = node.bodies[..] {
+                // dividing the weighted sum back may miss the position by rounding
+                node.center_of_mass = self.sources[body].0;
+            } else if node.mass > 0.0 {
+                node.center_of_mass *= 1.0 / node.mass;
+            }
+        }
+    }
+
+    fn insert(&mut self, body: usize) {
+        let (position, mass) = self.sources[body];
+        if mass <= 0.0 {
+            return;
+        }
+        let mut index = 0;
+        let mut depth = 0;
+        loop {
+            let node = &mut self.nodes[index];
+            let was_empty = node.mass == 0.0;
+            // the center of mass is a weighted sum until the tree is built
+            node.mass += mass;
+            node.center_of_mass.add_scaled(&position, mass);
+            if let Some(first_child) = node.first_child {
+                index = first_child + node.get_octant(&position);
+                depth += 1;
+                continue;
+            }
+            if was_empty || depth >= MAX_TREE_DEPTH {
+                node.bodies.push(body);
+                return;
+            }
+
+            // a leaf with a body, it's split and the body goes down to a child
+            let existing = node.bodies.pop();
+            let center = node.center;
+            let half_size = node.half_size * 0.5;
+            let first_child = self.nodes.len();
+            self.nodes[index].first_child = Some(first_child);
+            for octant in 0..8 {
+                let offset = |bit: usize| {
+                    if octant & bit == 0 {
+                        -half_size
+                    } else {
+                        half_size
+                    }
+                };
+                let child_center = center + Vec3::from_values(offset(1), offset(2), offset(4));
+                self.nodes.push(OctreeNode::new(child_center, half_size));
+            }
+            if let Some(existing) = existing {
+                let (existing_position, existing_mass) = self.sources[existing];
+                let child = first_child + self.nodes[index].get_octant(&existing_position);
+                let child = &mut self.nodes[child];
+                child.mass = existing_mass;
+                child.center_of_mass = &existing_position * existing_mass;
+                child.bodies.push(existing);
+            }
+            index = first_child + self.nodes[index].get_octant(&position);
+            depth += 1;
+        }
+    }
+
+    pub fn set_gravitational_constant(&mut self, gravitational_constant: Real) -> &mut Self {
+        self.gravitational_constant = gravitational_constant;
+        self
+    }
+
+    pub fn set_softening(&mut self, softening: Real) -> &mut Self {
+        self.softening = softening;
+        self
+    }
+
+    pub fn set_mode(&mut self, mode: NBodyMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn set_opening_angle(&mut self, opening_angle: Real) -> &mut Self {
+        self.opening_angle = opening_angle;
+        self
+    }
+}
+
+impl Default for NBodyGravity {
+    fn default() -> Self {
+        Self::new(GRAVITATIONAL_CONSTANT)
+    }
+}
+
+impl ForceGenerator for NBodyGravity {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
+        if particle.is_infinite_mass() {
+            return;
+        }
+        let field = self.get_field(&particle.get_position());
+        particle.add_force(&field * (particle.get_mass() * particle.get_gravity_scale()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    fn particle(x: Real, y: Real, z: Real, mass: Real) -> Particle {
+        let mut particle = Particle::new();
+        particle.set_mass(mass);
+        particle.set_position(Vec3::from_values(x, y, z));
+        particle
+    }
+
+    fn bodies() -> Vec<Particle> {
+        let mut particles: Vec<Particle> = (0..40)
+            .map(|i| {
+                let i = i as Real;
+                particle(
+                    (i * 0.37).sin(),
+                    (i * 1.13).cos(),
+                    (i * 0.71).sin() * 0.5,
+                    1.0 + i % 3.0,
+                )
+            })
+            .collect();
+        // closer than the smallest node, they end up in a leaf at the maximal depth
+        particles.push(particle(0.25, 0.25, 0.25, 2.0));
+        particles.push(particle(0.25 + 1.0e-12, 0.25, 0.25, 3.0));
+        particles.push(particle(0.25, 0.25 + 1.0e-12, 0.25, 1.0));
+        particles
+    }
+
+    fn fields(mode: NBodyMode, opening_angle: Real, particles: &[Particle]) -> Vec<Vec3> {
+        let mut gravity = NBodyGravity::new(1.0);
+        gravity
+            .set_mode(mode)
+            .set_opening_angle(opening_angle)
+            .set_softening(0.01);
+        gravity.update_sources(particles);
+        particles
+            .iter()
+            .map(|p| gravity.get_field(&p.get_position()))
+            .collect()
+    }
+
+    #[test]
+    fn barnes_hut_without_opening_angle_is_exact() {
+        let particles = bodies();
+        let exact = fields(NBodyMode::Exact, 0.5, &particles);
+        let tree = fields(NBodyMode::BarnesHut, 0.0, &particles);
+        for (e, t) in exact.iter().zip(tree.iter()) {
+            assert!(
+                (*e - *t).magnitude() < 1.0e-9 * e.magnitude(),
+                "{:?} {:?}",
+                e,
+                t
+            );
+        }
+    }
+
+    #[test]
+    fn barnes_hut_approximates_a_spread_cluster() {
+        // a cloud of bodies a few meters across, none of them close to another
+        let particles: Vec<Particle> = (0..64)
+            .map(|i| {
+                let coordinate = |n: usize| 2.0 * ((i / n) % 4) as Real;
+                let jitter = (i as Real * 0.37).sin() * 0.3;
+                particle(
+                    coordinate(1) + jitter,
+                    coordinate(4),
+                    coordinate(16) - jitter,
+                    1.0,
+                )
+            })
+            .collect();
+        let exact = fields(NBodyMode::Exact, 0.5, &particles);
+        let tree = fields(NBodyMode::BarnesHut, 0.5, &particles);
+        for (e, t) in exact.iter().zip(tree.iter()) {
+            assert!(
+                (*e - *t).magnitude() < 0.03 * e.magnitude(),
+                "{:?} {:?}",
+                e,
+                t
+            );
+        }
+    }
+
+    #[test]
+    fn softening_caps_the_attraction() {
+        let mut gravity = NBodyGravity::new(1.0);
+        gravity.set_mode(NBodyMode::BarnesHut).set_softening(0.1);
+        gravity.update_sources(&[particle(0.0, 0.0, 0.0, 2.0), particle(5.0, 5.0, 5.0, 1.0)]);
+        // the largest pull of a softened mass is 2 / (3 sqrt(3)) G m / e^2
+        let cap = 2.0 / (3.0 * (3.0 as Real).sqrt()) * 2.0 / (0.1 * 0.1);
+        for &distance in [1.0e-6, 1.0e-3, 0.05, 0.0707, 0.1, 1.0 as Real].iter() {
+            let field = gravity.get_field(&Vec3::from_values(distance, 0.0, 0.0));
+            assert!(
+                field.magnitude() <= cap * 1.001,
+                "{} {}",
+                distance,
+                field.magnitude()
+            );
+        }
+
+        gravity.set_softening(0.0);
+        gravity.update_sources(&[particle(0.0, 0.0, 0.0, 2.0), particle(5.0, 5.0, 5.0, 1.0)]);
+        let field = gravity.get_field(&Vec3::from_values(1.0e-3, 0.0, 0.0));
+        assert!(field.magnitude() > 1.0e6);
+    }
+}
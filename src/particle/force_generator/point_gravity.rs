@@ -0,0 +1,201 @@
+use crate::particle::force_generator::n_body_gravity::GRAVITATIONAL_CONSTANT;
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// A planet or any other body pulling everything towards its center
+#[derive(Copy, Clone, Debug)]
+pub struct GravitySource {
+    pub center: Vec3,
+    // G * M, in m^3 / s^2
+    pub gravitational_parameter: Real,
+    // inside of it the gravity falls linearly to zero at the center,
+    // like in a ball of the uniform density
+    pub radius: Real,
+}
+
+impl GravitySource {
+    pub fn new(center: Vec3, mass: Real, radius: Real) -> Self {
+        GravitySource {
+            center,
+            gravitational_parameter: GRAVITATIONAL_CONSTANT * mass,
+            radius,
+        }
+    }
+
+    /// Game planets are easier to tune by the gravity on their surface
+    pub fn from_surface_gravity(center: Vec3, radius: Real, surface_gravity: Real) -> Self {
+        GravitySource {
+            center,
+            gravitational_parameter: surface_gravity * radius * radius,
+            radius,
+        }
+    }
+
+    /// Gravity of the source at the position, inverse-square outside of the radius
+    pub fn get_acceleration(&self, position: &Vec3) -> Vec3 {
+        let offset = self.center - *position;
+        let distance = offset.magnitude();
+        if distance == 0.0 {
+            return Vec3::new();
+        }
+        let magnitude = if distance < self.radius {
+            self.gravitational_parameter * distance / (self.radius * self.radius * self.radius)
+        } else {
+            self.gravitational_parameter / (distance * distance)
+        };
+        &offset * (magnitude / distance)
+    }
+}
+
+/// Gravity towards one or several fixed or moving centers, so objects can walk
+/// around small planets and orbit them
+pub struct PointGravity {
+    sources: Vec<GravitySource>,
+}
+
+impl PointGravity {
+    pub fn new() -> Self {
+        PointGravity {
+            sources: Vec::new(),
+        }
+    }
+
+    pub fn add_source(&mut self, source: GravitySource) -> usize {
+        self.sources.push(source);
+        self.sources.len() - 1
+    }
+
+    pub fn remove_source(&mut self, index: usize) -> GravitySource {
+        self.sources.remove(index)
+    }
+
+    pub fn get_sources(&self) -> &[GravitySource] {
+        &self.sources
+    }
+
+    /// Planets on orbits are moved here
+    pub fn get_sources_mut(&mut self) -> &mut [GravitySource] {
+        &mut self.sources
+    }
+
+    /// The sum of the gravity of all sources at the position
+    pub fn get_acceleration(&self, position: &Vec3) -> Vec3 {
+        let mut acceleration = Vec3::new();
+        for source in self.sources.iter() {
+            acceleration += source.get_acceleration(position);
+        }
+        acceleration
+    }
+
+    /// The direction against the gravity, where a character standing at the position
+    /// should have its head. Zero where the gravity cancels out
+    pub fn get_up(&self, position: &Vec3) -> Vec3 {
+        let mut up = &self.get_acceleration(position) * -1.0;
+        up.normalize();
+        up
+    }
+}
+
+impl Default for PointGravity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ForceGenerator for PointGravity {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
+        if particle.is_infinite_mass() {
+            return;
+        }
+        let acceleration = self.get_acceleration(&particle.get_position());
+        particle.add_force(&acceleration * (particle.get_mass() * particle.get_gravity_scale()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(
+            (actual - expected).magnitude() < 1.0e-9 * expected.magnitude().max(1.0),
+            "{:?} is not {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn planet(x: Real) -> GravitySource {
+        GravitySource::from_surface_gravity(Vec3::from_values(x, 0.0, 0.0), 2.0, 5.0)
+    }
+
+    #[test]
+    fn gravity_is_inverse_square_outside_and_linear_inside() {
+        let source = planet(0.0);
+        // 5 m/s^2 on the surface from either side
+        assert_close(
+            source.get_acceleration(&Vec3::from_values(0.0, 2.0, 0.0)),
+            Vec3::from_values(0.0, -5.0, 0.0),
+        );
+        assert_close(
+            source.get_acceleration(&Vec3::from_values(0.0, 0.0, 6.0)),
+            Vec3::from_values(0.0, 0.0, -5.0 / 9.0),
+        );
+        assert_close(
+            source.get_acceleration(&Vec3::from_values(0.5, 0.0, 0.0)),
+            Vec3::from_values(-1.25, 0.0, 0.0),
+        );
+        assert_close(source.get_acceleration(&Vec3::new()), Vec3::new());
+
+        let by_mass = GravitySource::new(Vec3::new(), 3.0e10, 1.0);
+        assert_close(
+            by_mass.get_acceleration(&Vec3::from_values(10.0, 0.0, 0.0)),
+            Vec3::from_values(-GRAVITATIONAL_CONSTANT * 3.0e10 / 100.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn sources_add_up_and_cancel_between_planets() {
+        let mut gravity = PointGravity::new();
+        gravity.add_source(planet(-10.0));
+        let right = gravity.add_source(planet(10.0));
+
+        // in the middle the pulls cancel and there is no up
+        let middle = Vec3::new();
+        assert_close(gravity.get_acceleration(&middle), Vec3::new());
+        assert_close(gravity.get_up(&middle), Vec3::new());
+
+        // closer to the right planet its pull wins
+        let position = Vec3::from_values(4.0, 0.0, 0.0);
+        let expected = 20.0 / 36.0 - 20.0 / 196.0;
+        assert_close(
+            gravity.get_acceleration(&position),
+            Vec3::from_values(expected, 0.0, 0.0),
+        );
+        assert_close(gravity.get_up(&position), Vec3::from_values(-1.0, 0.0, 0.0));
+
+        gravity.remove_source(right);
+        assert_eq!(gravity.get_sources().len(), 1);
+        assert_close(
+            gravity.get_acceleration(&position),
+            Vec3::from_values(-20.0 / 196.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn force_is_scaled_by_the_mass_and_the_gravity_scale() {
+        let mut gravity = PointGravity::new();
+        gravity.add_source(planet(0.0));
+        let mut particle = Particle::new();
+        particle.set_mass(3.0).set_gravity_scale(0.5);
+        particle.set_position(Vec3::from_values(0.0, 4.0, 0.0));
+        gravity.update_force(&mut particle, 0.01);
+        assert_close(
+            particle.get_force_accum(),
+            Vec3::from_values(0.0, -1.875, 0.0),
+        );
+    }
+}
@@ -1,45 +1,172 @@
-// use crate::particle::force_generator::ForceGenerator;
-// use crate::particle::particle_trait::ParticleTrait;
-// use crate::rigid_body::RigidBody;
-// use crate::types::Real;
-// use crate::vector::Vec3;
-//
-// struct Spring<'a> {
-//     connection_point: Vec3,
-//     other_connection_point: Vec3,
-//     other: &'a RigidBody,
-//     spring_constant: Real,
-//     rest_length: Real,
-// }
-//
-// impl<'a> Spring<'a> {
-//     pub fn new(
-//         connection_point: Vec3,
-//         other: &'a RigidBody,
-//         other_connection_point: Vec3,
-//         spring_constant: Real,
-//         rest_length: Real,
-//     ) -> Self {
-//         Spring {
-//             connection_point,
-//             other,
-//             other_connection_point,
-//             spring_constant,
-//             rest_length,
-//         }
-//     }
-// }
-//
-// impl ForceGenerator for Spring<'_> {
-//     fn update_force(&mut self, body: &mut RigidBody, _duration: Real) {
-//         let lws = body.get_point_in_world_space(&self.connection_point);
-//         let ows = self
-//             .other
-//             .get_point_in_world_space(&self.other_connection_point);
-//
-//         let mut force = lws - ows;
-//         let magnitude = self.spring_constant * (force.magnitude() - self.rest_length).abs();
-//         force.normalize();
-//         body.add_force(&(&force * -magnitude));
-//     }
-// }
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// A damped spring between two particles, both ends get equal and opposite forces.
+/// Force generators update one particle at a time, so the spring is applied to pairs,
+/// or a ParticleSpring is registered for each end
+pub struct Spring {
+    spring_constant: Real,
+    rest_length: Real,
+    // resists the ends moving apart or together, in N * s / m
+    damping: Real,
+}
+
+impl Spring {
+    pub fn new(spring_constant: Real, rest_length: Real, damping: Real) -> Self {
+        Spring {
+            spring_constant,
+            rest_length,
+            damping,
+        }
+    }
+
+    pub fn set_spring_constant(&mut self, spring_constant: Real) -> &mut Self {
+        self.spring_constant = spring_constant;
+        self
+    }
+
+    pub fn set_rest_length(&mut self, rest_length: Real) -> &mut Self {
+        self.rest_length = rest_length;
+        self
+    }
+
+    pub fn set_damping(&mut self, damping: Real) -> &mut Self {
+        self.damping = damping;
+        self
+    }
+
+    /// The force on the first particle, the second one gets the opposite
+    pub fn get_force<A: ParticleTrait, B: ParticleTrait>(&self, first: &A, second: &B) -> Vec3 {
+        let mut direction = first.get_position() - second.get_position();
+        let length = direction.magnitude();
+        direction.normalize();
+        let relative_velocity = first.get_velocity() - second.get_velocity();
+        let magnitude = self.spring_constant * (length - self.rest_length)
+            + self.damping * (&relative_velocity * &direction);
+        &direction * -magnitude
+    }
+
+    pub fn update_forces<A: ParticleTrait, B: ParticleTrait>(
+        &self,
+        first: &mut A,
+        second: &mut B,
+        _duration: Real,
+    ) {
+        let force = self.get_force(first, second);
+        first.add_force(force);
+        second.add_force(&force * -1.0);
+    }
+
+    /// Applies the spring to two particles of the slice
+    pub fn update_pair<P: ParticleTrait>(
+        &self,
+        particles: &mut [P],
+        first: usize,
+        second: usize,
+        duration: Real,
+    ) {
+        if first == second {
+            return;
+        }
+        let (low, high) = particles.split_at_mut(first.max(second));
+        let (a, b) = if first < second {
+            (&mut low[first], &mut high[0])
+        } else {
+            (&mut high[0], &mut low[second])
+        };
+        self.update_forces(a, b, duration);
+    }
+}
+
+/// The spring as a force generator of one end, it holds the other end like Bangee and
+/// pushes or pulls only the particle it updates. Register one for each end
+pub struct ParticleSpring<'a, PT: ParticleTrait> {
+    other: &'a PT,
+    spring: Spring,
+}
+
+impl<'a, PT: ParticleTrait> ParticleSpring<'a, PT> {
+    pub fn new(other: &'a PT, spring_constant: Real, rest_length: Real) -> Self {
+        ParticleSpring {
+            other,
+            spring: Spring::new(spring_constant, rest_length, 0.0),
+        }
+    }
+
+    pub fn set_damping(&mut self, damping: Real) -> &mut Self {
+        self.spring.set_damping(damping);
+        self
+    }
+
+    pub fn get_spring_mut(&mut self) -> &mut Spring {
+        &mut self.spring
+    }
+}
+
+impl<'a, PT: ParticleTrait> ForceGenerator for ParticleSpring<'a, PT> {
+    fn update_force<P: ParticleTrait>(&mut self, particle: &mut P, _duration: Real) {
+        particle.add_force(self.spring.get_force(particle, self.other));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::force_generator::test_helpers;
+    use crate::particle::Particle;
+
+    fn particle(x: Real, velocity: Real) -> Particle {
+        test_helpers::particle(
+            Vec3::from_values(x, 0.0, 0.0),
+            Vec3::from_values(velocity, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn ends_get_equal_and_opposite_forces() {
+        let spring = Spring::new(10.0, 1.0, 2.0);
+        for &(x, velocity) in [(3.0, 0.0), (0.5, 0.0), (1.0, 4.0 as Real)].iter() {
+            let mut particles = [particle(0.0, 0.0), particle(x, velocity)];
+            spring.update_pair(&mut particles, 1, 0, 0.01);
+            let (first, second) = (
+                particles[0].get_force_accum(),
+                particles[1].get_force_accum(),
+            );
+            assert!((first + second).magnitude() < 1.0e-12);
+            // stretched, compressed or moving apart, the ends are pulled or pushed
+            let expected = 10.0 * (x - 1.0) + 2.0 * velocity;
+            assert!((first.x - expected).abs() < 1.0e-12);
+
+            let mut end = ParticleSpring::new(&particles[0], 10.0, 1.0);
+            end.set_damping(2.0);
+            let mut other_end = particle(x, velocity);
+            end.update_force(&mut other_end, 0.01);
+            assert!((other_end.get_force_accum() - second).magnitude() < 1.0e-12);
+        }
+    }
+
+    fn amplitude_after(damping: Real) -> Real {
+        let spring = Spring::new(10.0, 1.0, damping);
+        let mut particles = [particle(0.0, 0.0), particle(1.5, 0.0)];
+        let mut amplitude: Real = 0.0;
+        for step in 0..2000 {
+            spring.update_pair(&mut particles, 0, 1, 0.001);
+            for particle in particles.iter_mut() {
+                particle.integrate(0.001);
+            }
+            let length = (particles[1].get_position() - particles[0].get_position()).magnitude();
+            if step >= 1000 {
+                amplitude = amplitude.max((length - 1.0).abs());
+            }
+        }
+        amplitude
+    }
+
+    #[test]
+    fn damping_slows_the_oscillation_down() {
+        assert!((amplitude_after(0.0) - 0.5).abs() < 1.0e-2);
+        assert!(amplitude_after(1.0) < 0.2);
+    }
+}
@@ -0,0 +1,24 @@
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::particle::Particle;
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// A particle which keeps its velocity, so the tested forces are the only change
+pub fn particle(position: Vec3, velocity: Vec3) -> Particle {
+    let mut particle = Particle::new();
+    particle.set_damping(1.0);
+    particle.set_position(position);
+    particle.set_velocity(velocity);
+    particle
+}
+
+/// The force along x on a particle on the x axis
+pub fn force_at<F: ForceGenerator>(generator: &mut F, x: Real, velocity: Real) -> Real {
+    let mut particle = particle(
+        Vec3::from_values(x, 0.0, 0.0),
+        Vec3::from_values(velocity, 0.0, 0.0),
+    );
+    generator.update_force(&mut particle, 0.01);
+    particle.get_force_accum().x
+}
@@ -0,0 +1,435 @@
+use crate::particle::force_generator::gravity::Gravity;
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::particle::spatial_grid::SpatialGrid;
+use crate::particle::Particle;
+use crate::plane::Plane;
+use crate::types::Real;
+use crate::vector::Vec3;
+use crate::GRAVITY;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+// contacts with boundaries are keyed by the grain and usize::MAX - boundary index
+type ContactKey = (usize, usize);
+
+/// What a contact needs to know about a touching body, boundaries are bodies
+/// with infinite radius and mass
+struct Body {
+    velocity: Vec3,
+    angular_velocity: Vec3,
+    radius: Real,
+    mass: Real,
+}
+
+/// Discrete element method for sand, gravel and powders. Grains are spheres that
+/// overlap slightly when touching and push each other apart with Hertz-Mindlin
+/// contact forces, instead of having impulses resolved by the ContactResolver.
+/// The forces are stiff, so the material is stepped with a small fixed time step
+pub struct GranularMaterial {
+    particles: Vec<Particle>,
+    radii: Vec<Real>,
+    angular_velocities: Vec<Vec3>,
+    torques: Vec<Vec3>,
+    // tangential spring displacement of every touching pair, it carries
+    // static friction from step to step
+    contacts: HashMap<ContactKey, Vec3>,
+    grid: SpatialGrid,
+    boundaries: Vec<Plane>,
+    young_modulus: Real,
+    poisson_ratio: Real,
+    // the part of the normal velocity kept after a collision
+    restitution: Real,
+    friction: Real,
+    rolling_friction: Real,
+    // adhesive stress over the contact area, makes wet sand and powders clump
+    cohesion: Real,
+    time_step: Real,
+    // simulation time not stepped yet, less than one time step
+    time_accumulator: Real,
+    // time steps an update may take at most, the rest of a longer duration is dropped
+    max_steps: u32,
+}
+
+impl GranularMaterial {
+    pub fn new() -> Self {
+        Self {
+            particles: Vec::new(),
+            radii: Vec::new(),
+            angular_velocities: Vec::new(),
+            torques: Vec::new(),
+            contacts: HashMap::new(),
+            grid: SpatialGrid::new(1.0),
+            boundaries: Vec::new(),
+            // real sand is a thousand times stiffer, it would need a much smaller time step
+            young_modulus: 1.0e7,
+            poisson_ratio: 0.3,
+            restitution: 0.5,
+            friction: 0.5,
+            rolling_friction: 0.1,
+            cohesion: 0.0,
+            time_step: 1.0e-4,
+            time_accumulator: 0.0,
+            max_steps: 1000,
+        }
+    }
+
+    /// Adds a spherical grain, density is in kg per cubic meter
+    pub fn add_grain(&mut self, position: Vec3, radius: Real, density: Real) -> usize {
+        let mass = density * 4.0 / 3.0 * PI as Real * radius.powi(3);
+        let mut particle = Particle::new();
+        particle.set_mass(mass).set_damping(1.0);
+        particle.set_position(position);
+        self.particles.push(particle);
+        self.radii.push(radius);
+        self.angular_velocities.push(Vec3::new());
+        self.torques.push(Vec3::new());
+        let max_radius = self.radii.iter().cloned().fold(0.0, Real::max);
+        if 2.0 * max_radius > self.grid.get_cell_size() {
+            self.grid = SpatialGrid::new(2.0 * max_radius);
+        }
+        self.particles.len() - 1
+    }
+
+    pub fn add_boundary(&mut self, plane: Plane) {
+        self.boundaries.push(plane);
+    }
+
+    /// Steps the material as many fixed time steps as fit into the duration,
+    /// the rest is carried over to the next call. At most max_steps are taken,
+    /// the material falls behind after a long frame instead of catching up with it
+    pub fn update(&mut self, duration: Real) {
+        self.time_accumulator += duration;
+        let mut steps = 0;
+        while self.time_accumulator >= self.time_step {
+            if steps == self.max_steps {
+                // catching up would make the next frame even longer, e.g. after a pause
+                self.time_accumulator = 0.0;
+                return;
+            }
+            self.step(self.time_step);
+            self.time_accumulator -= self.time_step;
+            steps += 1;
+        }
+    }
+
+    fn step(&mut self, duration: Real) {
+        let positions: Vec<Vec3> = self.particles.iter().map(|p| p.get_position()).collect();
+        self.grid.rebuild(positions.iter());
+        let mut pairs = Vec::new();
+        for (i, position) in positions.iter().enumerate() {
+            let radii = &self.radii;
+            self.grid.for_each_candidate(position, |j| {
+                if j > i && (positions[j] - *position).magnitude() < radii[i] + radii[j] {
+                    pairs.push((i, j));
+                }
+            });
+        }
+
+        let mut contacts = HashMap::with_capacity(self.contacts.len());
+        for (i, j) in pairs {
+            let mut normal = positions[i] - positions[j];
+            let distance = normal.magnitude();
+            if distance == 0.0 {
+                continue;
+            }
+            normal *= 1.0 / distance;
+            let overlap = self.radii[i] + self.radii[j] - distance;
+            let other = self.get_body(j);
+            let (force, torque_i, torque_j, spring) =
+                self.calculate_contact(i, &other, normal, overlap, (i, j), duration);
+            self.particles[i].add_force(force);
+            self.particles[j].add_force(&force * -1.0);
+            self.torques[i] += torque_i;
+            self.torques[j] += torque_j;
+            contacts.insert((i, j), spring);
+        }
+        for (i, position) in positions.iter().enumerate() {
+            for k in 0..self.boundaries.len() {
+                let plane = self.boundaries[k];
+                let overlap = self.radii[i] - plane.distance(position);
+                if overlap <= 0.0 {
+                    continue;
+                }
+                let wall = Body {
+                    velocity: Vec3::new(),
+                    angular_velocity: Vec3::new(),
+                    radius: Real::INFINITY,
+                    mass: Real::INFINITY,
+                };
+                let key = (i, usize::MAX - k);
+                let (force, torque, _, spring) =
+                    self.calculate_contact(i, &wall, plane.normal, overlap, key, duration);
+                self.particles[i].add_force(force);
+                self.torques[i] += torque;
+                contacts.insert(key, spring);
+            }
+        }
+        // pairs that separated forget their tangential springs
+        self.contacts = contacts;
+
+        let mut gravity = Gravity::new(GRAVITY);
+        for i in 0..self.particles.len() {
+            let particle = &mut self.particles[i];
+            gravity.update_force(particle, duration);
+            if !particle.is_infinite_mass() {
+                // moment of inertia of a solid sphere
+                let inertia = 0.4 * particle.get_mass() * self.radii[i] * self.radii[i];
+                self.angular_velocities[i].add_scaled(&self.torques[i], duration / inertia);
+            }
+            self.torques[i].set_to_zero();
+            particle.integrate_symplectic(duration);
+        }
+    }
+
+    /// Returns the force on the grain, torques on both bodies and the new tangential
+    /// spring. The normal points from the other body to the grain
+    fn calculate_contact(
+        &self,
+        i: usize,
+        other: &Body,
+        normal: Vec3,
+        overlap: Real,
+        key: ContactKey,
+        duration: Real,
+    ) -> (Vec3, Vec3, Vec3, Vec3) {
+        let grain = self.get_body(i);
+        let radius = effective(grain.radius, other.radius);
+        let mass = effective(grain.mass, other.mass);
+        let young_modulus = self.young_modulus / (2.0 * (1.0 - self.poisson_ratio.powi(2)));
+        let shear_modulus = self.young_modulus / (2.0 * (1.0 + self.poisson_ratio));
+        let shear_modulus = shear_modulus / (2.0 * (2.0 - self.poisson_ratio));
+        let contact_radius = (radius * overlap).sqrt();
+        let normal_stiffness = 2.0 * young_modulus * contact_radius;
+        let tangential_stiffness = 8.0 * shear_modulus * contact_radius;
+        let log_restitution = self.restitution.max(1.0e-3).ln();
+        let beta = log_restitution / (log_restitution.powi(2) + (PI as Real).powi(2)).sqrt();
+        let damping_factor = -2.0 * (5.0 as Real / 6.0).sqrt() * beta;
+
+        // velocity of the grain surface relative to the other surface at the contact point
+        let contact_velocity = grain.velocity + grain.angular_velocity % (&normal * -grain.radius)
+            - other.velocity
+            - if other.radius.is_finite() {
+                other.angular_velocity % (&normal * other.radius)
+            } else {
+                Vec3::new()
+            };
+        let normal_speed = &contact_velocity * &normal;
+        let tangential_velocity = contact_velocity - &normal * normal_speed;
+
+        let elastic_force = 4.0 / 3.0 * young_modulus * radius.sqrt() * overlap.powf(1.5);
+        let damping_force = damping_factor * (normal_stiffness * mass).sqrt() * normal_speed;
+        let cohesion_force = self.cohesion * PI as Real * contact_radius * contact_radius;
+        let normal_force = elastic_force - damping_force - cohesion_force;
+
+        let mut spring = self.contacts.get(&key).cloned().unwrap_or_default();
+        // the contact may have rotated since the last step
+        spring = spring - &normal * (&spring * &normal);
+        spring.add_scaled(&tangential_velocity, duration);
+        let mut tangential_force = &spring * -tangential_stiffness;
+        tangential_force.add_scaled(
+            &tangential_velocity,
+            -damping_factor * (tangential_stiffness * mass).sqrt(),
+        );
+        let max_friction = self.friction * elastic_force.max(0.0);
+        let tangential_magnitude = tangential_force.magnitude();
+        if tangential_magnitude > max_friction {
+            // sliding, the spring is shortened to the length the friction can hold
+            tangential_force *= max_friction / tangential_magnitude;
+            spring = &tangential_force * (-1.0 / tangential_stiffness);
+        }
+
+        let mut torque_i = (&normal * -grain.radius) % tangential_force;
+        let mut torque_j = if other.radius.is_finite() {
+            (&normal * other.radius) % (&tangential_force * -1.0)
+        } else {
+            Vec3::new()
+        };
+        let mut relative_rotation = grain.angular_velocity - other.angular_velocity;
+        if relative_rotation.magnitude() > 0.0 {
+            relative_rotation.normalize();
+            let rolling_torque =
+                &relative_rotation * (self.rolling_friction * elastic_force.max(0.0) * radius);
+            torque_i -= rolling_torque;
+            torque_j += rolling_torque;
+        }
+
+        let force = &normal * normal_force + tangential_force;
+        (force, torque_i, torque_j, spring)
+    }
+
+    fn get_body(&self, i: usize) -> Body {
+        Body {
+            velocity: self.particles[i].get_velocity(),
+            angular_velocity: self.angular_velocities[i],
+            radius: self.radii[i],
+            mass: self.particles[i].get_mass(),
+        }
+    }
+
+    pub fn set_young_modulus(&mut self, young_modulus: Real) -> &mut Self {
+        self.young_modulus = young_modulus;
+        self
+    }
+
+    pub fn set_poisson_ratio(&mut self, poisson_ratio: Real) -> &mut Self {
+        self.poisson_ratio = poisson_ratio;
+        self
+    }
+
+    pub fn set_restitution(&mut self, restitution: Real) -> &mut Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn set_friction(&mut self, friction: Real) -> &mut Self {
+        self.friction = friction;
+        self
+    }
+
+    pub fn set_rolling_friction(&mut self, rolling_friction: Real) -> &mut Self {
+        self.rolling_friction = rolling_friction;
+        self
+    }
+
+    pub fn set_cohesion(&mut self, cohesion: Real) -> &mut Self {
+        self.cohesion = cohesion;
+        self
+    }
+
+    pub fn set_time_step(&mut self, time_step: Real) -> &mut Self {
+        if time_step <= 0.0 {
+            panic!("Time step should be greater then 0");
+        }
+        self.time_step = time_step;
+        self
+    }
+
+    pub fn set_max_steps(&mut self, max_steps: u32) -> &mut Self {
+        if max_steps == 0 {
+            panic!("Max steps should be greater then 0");
+        }
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn get_particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn get_particles_mut(&mut self) -> &mut [Particle] {
+        &mut self.particles
+    }
+
+    pub fn get_radii(&self) -> &[Real] {
+        &self.radii
+    }
+
+    pub fn get_angular_velocities(&self) -> &[Vec3] {
+        &self.angular_velocities
+    }
+
+    /// The number of touching pairs, boundary contacts included
+    pub fn get_contact_count(&self) -> usize {
+        self.contacts.len()
+    }
+}
+
+impl Default for GranularMaterial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Effective value of two bodies in contact, e.g. the reduced mass.
+/// An infinite value, like the one of a boundary, leaves the other one
+fn effective(a: Real, b: Real) -> Real {
+    if a.is_infinite() {
+        return b;
+    }
+    if b.is_infinite() {
+        return a;
+    }
+    a * b / (a + b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_frames_are_capped() {
+        let mut material = GranularMaterial::new();
+        material.set_time_step(1.0e-3).set_max_steps(100);
+        material.add_grain(Vec3::new(), 0.01, 2500.0);
+        // a pause of 10 seconds, only 0.1 s is simulated
+        material.update(10.0);
+        let speed = material.get_particles()[0].get_velocity().magnitude();
+        assert!((speed - GRAVITY.magnitude() * 0.1).abs() < 1.0e-9);
+        // the rest of the pause is dropped, not carried over
+        material.update(0.0);
+        let next_speed = material.get_particles()[0].get_velocity().magnitude();
+        assert_eq!(next_speed, speed);
+    }
+
+    #[test]
+    fn pile_settles() {
+        let mut material = GranularMaterial::new();
+        material.set_time_step(2.5e-4);
+        let radius = 0.01;
+        let half_width = 0.03;
+        material.add_boundary(Plane::new(Vec3::from_values(0.0, 1.0, 0.0), 0.0));
+        for axis in [
+            Vec3::from_values(1.0, 0.0, 0.0),
+            Vec3::from_values(0.0, 0.0, 1.0),
+        ]
+        .iter()
+        {
+            material.add_boundary(Plane::from_point(*axis, axis * -half_width));
+            material.add_boundary(Plane::from_point(axis * -1.0, axis * half_width));
+        }
+        for i in 0..12 {
+            let (x, y, z) = (i % 2, i / 4, (i / 2) % 2);
+            // slightly off a grid, so the grains don't stack in perfect columns
+            let jitter = 0.002 * ((i * 7) % 5) as Real;
+            material.add_grain(
+                Vec3::from_values(
+                    -0.012 + 0.024 * x as Real + jitter,
+                    0.02 + 0.03 * y as Real,
+                    -0.012 + 0.024 * z as Real - jitter,
+                ),
+                radius,
+                2500.0,
+            );
+        }
+        for _ in 0..120 {
+            material.update(1.0 / 60.0);
+        }
+        for particle in material.get_particles() {
+            let position = particle.get_position();
+            assert!(particle.get_velocity().magnitude() < 1.0e-3);
+            assert!(position.y > radius * 0.9 && position.y < 0.1);
+            assert!(position.x.abs() < half_width && position.z.abs() < half_width);
+        }
+    }
+
+    #[test]
+    fn gravity_scale_changes_the_fall() {
+        let mut material = GranularMaterial::new();
+        material.set_time_step(1.0e-3);
+        for (i, &scale) in [1.0, 0.5, 0.0 as Real].iter().enumerate() {
+            material.add_grain(Vec3::from_values(i as Real, 0.0, 0.0), 0.01, 2500.0);
+            material.get_particles_mut()[i].set_gravity_scale(scale);
+        }
+        material.update(0.1 + 1.0e-6);
+        let speeds: Vec<Real> = material
+            .get_particles()
+            .iter()
+            .map(|particle| particle.get_velocity().magnitude())
+            .collect();
+        let full_speed = GRAVITY.magnitude() * 0.1;
+        assert!((speeds[0] - full_speed).abs() < 1.0e-9);
+        assert!((speeds[1] - full_speed / 2.0).abs() < 1.0e-9);
+        assert_eq!(speeds[2], 0.0);
+    }
+}
@@ -0,0 +1,374 @@
+use crate::particle::force_generator::gravity::Gravity;
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::mass_spring::{
+    apply_spring_forces, calculate_vertex_normals, tear_springs, SpringEdge,
+};
+use crate::particle::particle_trait::ParticleTrait;
+use crate::particle::Particle;
+use crate::types::Real;
+use crate::vector::Vec3;
+use crate::GRAVITY;
+
+pub struct ClothBuilder {
+    columns: usize,
+    rows: usize,
+    // distance between neighbouring particles
+    spacing: Real,
+    // position of the first particle
+    origin: Vec3,
+    // directions the columns and the rows of the grid go along
+    column_axis: Vec3,
+    row_axis: Vec3,
+    particle_mass: Real,
+    damping: Real,
+    // gravity, every particle gets it scaled by its gravity scale
+    acceleration: Vec3,
+    // springs between direct neighbours, they keep the cloth from stretching
+    structural_stiffness: Real,
+    // diagonal springs, they keep the cloth from shearing
+    shear_stiffness: Real,
+    // springs over one particle, they keep the cloth from folding
+    bending_stiffness: Real,
+    // springs stretched by more than this part of their rest length break
+    tear_strain: Option<Real>,
+}
+
+impl ClothBuilder {
+    /// columns and rows are the number of particles along each side of the grid
+    pub fn new(columns: usize, rows: usize) -> Self {
+        if columns < 2 || rows < 2 {
+            panic!("Cloth should have at least 2 columns and 2 rows");
+        }
+        Self {
+            columns,
+            rows,
+            spacing: 0.1,
+            origin: Vec3::new(),
+            column_axis: Vec3::from_values(1.0, 0.0, 0.0),
+            row_axis: Vec3::from_values(0.0, 0.0, 1.0),
+            particle_mass: 0.01,
+            damping: 0.9,
+            acceleration: GRAVITY,
+            structural_stiffness: 50.0,
+            shear_stiffness: 20.0,
+            bending_stiffness: 5.0,
+            tear_strain: None,
+        }
+    }
+
+    pub fn set_spacing(&mut self, spacing: Real) -> &mut Self {
+        self.spacing = spacing;
+        self
+    }
+
+    pub fn set_origin(&mut self, origin: Vec3) -> &mut Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Sets the plane the cloth is created in, the axes are normalized
+    pub fn set_axes(&mut self, mut column_axis: Vec3, mut row_axis: Vec3) -> &mut Self {
+        column_axis.normalize();
+        row_axis.normalize();
+        self.column_axis = column_axis;
+        self.row_axis = row_axis;
+        self
+    }
+
+    pub fn set_particle_mass(&mut self, mass: Real) -> &mut Self {
+        if mass <= 0.0 {
+            panic!("Mass should be greater then 0");
+        }
+        self.particle_mass = mass;
+        self
+    }
+
+    pub fn set_damping(&mut self, damping: Real) -> &mut Self {
+        self.damping = damping;
+        self
+    }
+
+    /// Gravity of the particles, it's scaled by the gravity scale of each one
+    pub fn set_acceleration(&mut self, acceleration: Vec3) -> &mut Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    pub fn set_stiffness(&mut self, structural: Real, shear: Real, bending: Real) -> &mut Self {
+        self.structural_stiffness = structural;
+        self.shear_stiffness = shear;
+        self.bending_stiffness = bending;
+        self
+    }
+
+    /// The cloth rips where springs are stretched by more than strain times their rest length
+    pub fn set_tear_strain(&mut self, strain: Real) -> &mut Self {
+        self.tear_strain = Some(strain);
+        self
+    }
+
+    pub fn build(&self) -> Cloth {
+        let mut particles = Vec::with_capacity(self.columns * self.rows);
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let mut particle = Particle::new();
+                particle
+                    .set_mass(self.particle_mass)
+                    .set_damping(self.damping);
+                particle.set_position(
+                    self.origin
+                        + &self.column_axis * (column as Real * self.spacing)
+                        + &self.row_axis * (row as Real * self.spacing),
+                );
+                particles.push(particle);
+            }
+        }
+
+        let index = |column: usize, row: usize| row * self.columns + column;
+        let mut springs = Vec::new();
+        let mut triangles = Vec::new();
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let i = index(column, row);
+                if column + 1 < self.columns {
+                    springs.push(SpringEdge::new(
+                        &particles,
+                        i,
+                        index(column + 1, row),
+                        self.structural_stiffness,
+                    ));
+                }
+                if row + 1 < self.rows {
+                    springs.push(SpringEdge::new(
+                        &particles,
+                        i,
+                        index(column, row + 1),
+                        self.structural_stiffness,
+                    ));
+                }
+                if column + 1 < self.columns && row + 1 < self.rows {
+                    let right = index(column + 1, row);
+                    let bottom = index(column, row + 1);
+                    let diagonal = index(column + 1, row + 1);
+                    springs.push(SpringEdge::new(
+                        &particles,
+                        i,
+                        diagonal,
+                        self.shear_stiffness,
+                    ));
+                    springs.push(SpringEdge::new(
+                        &particles,
+                        right,
+                        bottom,
+                        self.shear_stiffness,
+                    ));
+                    triangles.push([i, bottom, diagonal]);
+                    triangles.push([i, diagonal, right]);
+                }
+                if column + 2 < self.columns {
+                    let mut spring = SpringEdge::new(
+                        &particles,
+                        i,
+                        index(column + 2, row),
+                        self.bending_stiffness,
+                    );
+                    // folding compresses bending springs, they have to push back
+                    spring.resists_compression = true;
+                    springs.push(spring);
+                }
+                if row + 2 < self.rows {
+                    let mut spring = SpringEdge::new(
+                        &particles,
+                        i,
+                        index(column, row + 2),
+                        self.bending_stiffness,
+                    );
+                    spring.resists_compression = true;
+                    springs.push(spring);
+                }
+            }
+        }
+
+        for spring in springs.iter_mut() {
+            spring.breaking_strain = self.tear_strain;
+        }
+
+        let normals = calculate_vertex_normals(&particles, &triangles);
+        Cloth {
+            particles,
+            springs,
+            triangles,
+            normals,
+            columns: self.columns,
+            rows: self.rows,
+            particle_mass: self.particle_mass,
+            acceleration: self.acceleration,
+            torn_spring_count: 0,
+        }
+    }
+}
+
+/// A grid of particles connected by structural, shear and bending springs
+pub struct Cloth {
+    particles: Vec<Particle>,
+    springs: Vec<SpringEdge>,
+    // counter clockwise triangles for rendering, indices of particles
+    triangles: Vec<[usize; 3]>,
+    normals: Vec<Vec3>,
+    columns: usize,
+    rows: usize,
+    particle_mass: Real,
+    acceleration: Vec3,
+    torn_spring_count: usize,
+}
+
+impl Cloth {
+    pub fn update(&mut self, duration: Real) {
+        apply_spring_forces(&mut self.particles, &self.springs, duration);
+        let mut gravity = Gravity::new(self.acceleration);
+        for particle in self.particles.iter_mut() {
+            gravity.update_force(particle, duration);
+            particle.integrate_symplectic(duration);
+        }
+        self.torn_spring_count +=
+            tear_springs(&self.particles, &mut self.springs, &mut self.triangles);
+        self.normals = calculate_vertex_normals(&self.particles, &self.triangles);
+    }
+
+    /// Pinned particles get infinite mass, so they stay where they are unless moved by hand
+    pub fn pin(&mut self, index: usize) {
+        self.particles[index].set_inverse_mass(0.0);
+        self.particles[index].set_velocity(Vec3::new());
+    }
+
+    pub fn unpin(&mut self, index: usize) {
+        self.particles[index].set_mass(self.particle_mass);
+    }
+
+    pub fn is_pinned(&self, index: usize) -> bool {
+        self.particles[index].is_infinite_mass()
+    }
+
+    pub fn index(&self, column: usize, row: usize) -> usize {
+        row * self.columns + column
+    }
+
+    pub fn get_columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn get_rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn get_particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn get_particles_mut(&mut self) -> &mut [Particle] {
+        &mut self.particles
+    }
+
+    pub fn get_springs(&self) -> &[SpringEdge] {
+        &self.springs
+    }
+
+    /// The number of springs that have been torn apart since the cloth was built,
+    /// when it changes the triangle list has to be uploaded for rendering again
+    pub fn get_torn_spring_count(&self) -> usize {
+        self.torn_spring_count
+    }
+
+    pub fn get_triangles(&self) -> &[[usize; 3]] {
+        &self.triangles
+    }
+
+    /// Vertex normals, they are recalculated every update
+    pub fn get_normals(&self) -> &[Vec3] {
+        &self.normals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a sheet pinned along its first two columns with the last column folded up
+    // by 90 degrees, structural and shear springs keep their length, only the
+    // bending springs over the fourth column get compressed
+    fn folded_sheet(bending_stiffness: Real) -> Cloth {
+        let mut builder = ClothBuilder::new(5, 5);
+        builder
+            .set_damping(0.3)
+            .set_acceleration(Vec3::new())
+            .set_stiffness(50.0, 20.0, bending_stiffness);
+        let mut cloth = builder.build();
+        for row in 0..5 {
+            cloth.pin(cloth.index(0, row));
+            cloth.pin(cloth.index(1, row));
+            let folded = cloth.index(4, row);
+            cloth.get_particles_mut()[folded].set_position(Vec3::from_values(
+                0.3,
+                0.1,
+                0.1 * row as Real,
+            ));
+        }
+        for _ in 0..20000 {
+            cloth.update(0.001);
+        }
+        cloth
+    }
+
+    #[test]
+    fn bending_springs_unfold_the_sheet() {
+        let cloth = folded_sheet(5.0);
+        for row in 0..5 {
+            for column in 0..5 {
+                let position = cloth.get_particles()[cloth.index(column, row)].get_position();
+                let flat = Vec3::from_values(0.1 * column as Real, 0.0, 0.1 * row as Real);
+                assert!((position - flat).magnitude() < 0.02, "{:?}", position);
+            }
+        }
+    }
+
+    #[test]
+    fn tears_only_at_the_overstretched_springs() {
+        let mut builder = ClothBuilder::new(5, 5);
+        builder.set_acceleration(Vec3::new()).set_tear_strain(0.5);
+        let mut cloth = builder.build();
+        let spring_count = cloth.get_springs().len();
+        // the corner is pulled far out, every spring it is attached to overstretches
+        let corner = cloth.index(4, 4);
+        cloth.get_particles_mut()[corner].set_position(Vec3::from_values(1.0, 0.0, 1.0));
+        cloth.update(1.0e-6);
+
+        assert_eq!(
+            cloth.get_springs().len() + cloth.get_torn_spring_count(),
+            spring_count
+        );
+        assert!(cloth.get_torn_spring_count() > 0);
+        for spring in cloth.get_springs() {
+            assert!(spring.particles.0 != corner && spring.particles.1 != corner);
+        }
+        assert!(cloth.get_triangles().iter().all(|t| !t.contains(&corner)));
+        // springs away from the corner are all still there
+        let far_from_corner = |index: usize| index % 5 < 3 || index / 5 < 3;
+        let far_springs = |springs: &[SpringEdge]| {
+            springs
+                .iter()
+                .filter(|s| far_from_corner(s.particles.0) && far_from_corner(s.particles.1))
+                .count()
+        };
+        assert_eq!(
+            far_springs(cloth.get_springs()),
+            far_springs(&builder.build().springs)
+        );
+    }
+
+    #[test]
+    fn without_bending_stiffness_the_fold_stays() {
+        let cloth = folded_sheet(0.0);
+        let tip = cloth.get_particles()[cloth.index(4, 2)].get_position();
+        assert!((tip - Vec3::from_values(0.3, 0.1, 0.2)).magnitude() < 1.0e-6);
+    }
+}
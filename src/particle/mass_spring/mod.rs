@@ -0,0 +1,171 @@
+pub mod cloth;
+pub mod pressure_body;
+
+use crate::particle::force_generator::bangee::Bangee;
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::particle::Particle;
+use crate::types::Real;
+use crate::vector::Vec3;
+use std::collections::HashMap;
+
+/// A spring between two particles of a mass-spring body, particles are referenced by index
+#[derive(Copy, Clone, Debug)]
+pub struct SpringEdge {
+    pub particles: (usize, usize),
+    pub spring_constant: Real,
+    pub rest_length: Real,
+    // the spring breaks when stretched by more than this part of its rest length.
+    // It's the fracture counterpart of the DeformableSpring limit of elasticity
+    pub breaking_strain: Option<Real>,
+    // bungees only pull, springs which have to push back when compressed,
+    // like the bending springs of cloth, set it
+    pub resists_compression: bool,
+}
+
+impl SpringEdge {
+    /// The spring is at rest in the current configuration of the particles
+    pub fn new(particles: &[Particle], a: usize, b: usize, spring_constant: Real) -> Self {
+        let rest_length = (particles[a].get_position() - particles[b].get_position()).magnitude();
+        Self {
+            particles: (a, b),
+            spring_constant,
+            rest_length,
+            breaking_strain: None,
+            resists_compression: false,
+        }
+    }
+
+    pub fn current_length(&self, particles: &[Particle]) -> Real {
+        (particles[self.particles.0].get_position() - particles[self.particles.1].get_position())
+            .magnitude()
+    }
+
+    /// Relative elongation, zero at rest length and negative when compressed
+    pub fn get_strain(&self, particles: &[Particle]) -> Real {
+        (self.current_length(particles) - self.rest_length) / self.rest_length
+    }
+
+    pub fn is_overstrained(&self, particles: &[Particle]) -> bool {
+        match self.breaking_strain {
+            Some(breaking_strain) => self.get_strain(particles) > breaking_strain,
+            None => false,
+        }
+    }
+}
+
+/// Applies every spring to both of its ends. Bungees are used unless the spring resists
+/// compression, because fabric and membranes resist stretching, but fold freely
+pub(crate) fn apply_spring_forces(
+    particles: &mut [Particle],
+    springs: &[SpringEdge],
+    duration: Real,
+) {
+    for spring in springs {
+        let (a, b) = spring.particles;
+        if spring.resists_compression {
+            // both ends get equal and opposite forces, pulling or pushing
+            let mut direction = particles[a].get_position() - particles[b].get_position();
+            let length = direction.magnitude();
+            direction.normalize();
+            let force = &direction * (-spring.spring_constant * (length - spring.rest_length));
+            particles[a].add_force(force);
+            particles[b].add_force(&force * -1.0);
+            continue;
+        }
+        // particles are copied, the generators can't borrow the slice while it is mutated
+        let first = particles[a];
+        let second = particles[b];
+        Bangee::new(&second, spring.spring_constant, spring.rest_length)
+            .update_force(&mut particles[a], duration);
+        Bangee::new(&first, spring.spring_constant, spring.rest_length)
+            .update_force(&mut particles[b], duration);
+    }
+}
+
+/// Removes springs stretched beyond their breaking strain, together with the triangles
+/// that contain both ends of a removed spring. Springs spanning the tear, like shear and
+/// bending springs over the removed triangles, go as well, a spring stays only while its
+/// ends are on the same triangle or on triangles at most two edge neighbours apart.
+/// Returns the number of removed springs
+pub(crate) fn tear_springs(
+    particles: &[Particle],
+    springs: &mut Vec<SpringEdge>,
+    triangles: &mut Vec<[usize; 3]>,
+) -> usize {
+    let mut torn = Vec::new();
+    springs.retain(|spring| {
+        if spring.is_overstrained(particles) {
+            torn.push(spring.particles);
+            return false;
+        }
+        true
+    });
+    if torn.is_empty() {
+        return 0;
+    }
+    triangles.retain(|triangle| {
+        !torn
+            .iter()
+            .any(|(a, b)| triangle.contains(a) && triangle.contains(b))
+    });
+
+    let mut vertex_triangles = vec![Vec::new(); particles.len()];
+    let mut edge_triangles: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (t, triangle) in triangles.iter().enumerate() {
+        for k in 0..3 {
+            let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+            vertex_triangles[a].push(t);
+            edge_triangles
+                .entry((a.min(b), a.max(b)))
+                .or_default()
+                .push(t);
+        }
+    }
+    // triangles sharing an edge with each triangle, itself included
+    let neighbours: Vec<Vec<usize>> = triangles
+        .iter()
+        .map(|triangle| {
+            (0..3)
+                .flat_map(|k| {
+                    let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+                    edge_triangles[&(a.min(b), a.max(b))].iter().copied()
+                })
+                .collect()
+        })
+        .collect();
+    let count_before = springs.len();
+    springs.retain(|spring| {
+        let (a, b) = spring.particles;
+        vertex_triangles[a].iter().any(|&first| {
+            neighbours[first].iter().any(|&second| {
+                neighbours[second]
+                    .iter()
+                    .any(|&third| triangles[third].contains(&b))
+            })
+        })
+    });
+    torn.len() + count_before - springs.len()
+}
+
+/// Area weighted vertex normals of a triangle mesh with counter clockwise winding
+pub(crate) fn calculate_vertex_normals(
+    particles: &[Particle],
+    triangles: &[[usize; 3]],
+) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::new(); particles.len()];
+    for triangle in triangles {
+        let p0 = particles[triangle[0]].get_position();
+        let p1 = particles[triangle[1]].get_position();
+        let p2 = particles[triangle[2]].get_position();
+        // the magnitude of the cross product is twice the triangle area
+        let face_normal = (p1 - p0) % (p2 - p0);
+        for &vertex in triangle {
+            normals[vertex] += face_normal;
+        }
+    }
+    for normal in normals.iter_mut() {
+        normal.normalize();
+    }
+    normals
+}
@@ -1,8 +1,15 @@
 pub mod collision;
+pub mod emitter;
 pub mod force_generator;
 pub mod force_registry;
+pub mod granular;
+pub mod mass_spring;
 pub mod particle_trait;
+pub mod shape_matching;
+pub mod spatial_grid;
+pub mod sph;
 pub mod world;
+pub mod xpbd;
 
 use crate::particle::particle_trait::ParticleTrait;
 use crate::types::Real;
@@ -26,6 +33,10 @@ pub struct Particle {
     /// infinity) and we can represent objects with infinite mass saying inverse mass = 0
     /// this can be used for immovable objects like walls or floor
     inverse_mass: Real,
+    /// Electric charge in coulombs, used by electromagnetic force generators
+    charge: Real,
+    /// Multiplies the force of gravity generators, 0 makes the particle weightless
+    gravity_scale: Real,
     force_accum: Vec3,
 }
 
@@ -37,6 +48,8 @@ impl Particle {
             acceleration: Vec3::new(),
             damping: 0.999,
             inverse_mass: 1.0,
+            charge: 0.0,
+            gravity_scale: 1.0,
             force_accum: Vec3::new(),
         }
     }
@@ -77,6 +90,16 @@ impl Particle {
         self.damping = damping;
         self
     }
+
+    pub fn set_charge(&mut self, charge: Real) -> &mut Self {
+        self.charge = charge;
+        self
+    }
+
+    pub fn set_gravity_scale(&mut self, gravity_scale: Real) -> &mut Self {
+        self.gravity_scale = gravity_scale;
+        self
+    }
 }
 
 impl ParticleTrait for Particle {
@@ -127,4 +150,12 @@ impl ParticleTrait for Particle {
     fn get_force_accum(&self) -> Vec3 {
         self.force_accum
     }
+
+    fn get_charge(&self) -> Real {
+        self.charge
+    }
+
+    fn get_gravity_scale(&self) -> Real {
+        self.gravity_scale
+    }
 }
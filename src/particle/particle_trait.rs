@@ -9,6 +9,8 @@ pub trait ParticleTrait {
     /// mey be inaccurate in some cases
     fn integrate(&mut self, duration: Real) -> &mut Self {
         if self.is_infinite_mass() || duration == 0.0 {
+            // forces of a pinned particle must not pile up until it's released
+            self.clear_accumulator();
             return self;
         }
 
@@ -28,6 +30,32 @@ pub trait ParticleTrait {
         self
     }
 
+    /// Semi-implicit (symplectic) Euler integration, the velocity is updated first and
+    /// the position is moved with the new velocity. It costs the same as integrate,
+    /// but doesn't gain energy, so stiff springs and orbits stay stable
+    fn integrate_symplectic(&mut self, duration: Real) -> &mut Self {
+        if self.is_infinite_mass() || duration == 0.0 {
+            // forces of a pinned particle must not pile up until it's released
+            self.clear_accumulator();
+            return self;
+        }
+
+        let mut resulting_acceleration = self.get_acceleration();
+        resulting_acceleration.add_scaled(&self.get_force_accum(), self.get_inverse_mass());
+
+        let mut next_velocity = self.get_velocity();
+        next_velocity.add_scaled(&resulting_acceleration, duration);
+        next_velocity *= self.get_damping().powf(duration);
+        self.set_velocity(next_velocity);
+
+        let mut next_position = self.get_position();
+        next_position.add_scaled(&next_velocity, duration);
+        self.set_position(next_position);
+
+        self.clear_accumulator();
+        self
+    }
+
     fn get_mass(&self) -> Real {
         if self.is_infinite_mass() {
             return INFINITY as Real;
@@ -51,4 +79,14 @@ pub trait ParticleTrait {
     fn add_force(&mut self, f: Vec3) -> &mut Self;
     // TODO should I return links from all getters?
     fn get_force_accum(&self) -> Vec3;
+
+    /// Electric charge in coulombs, particles are neutral unless they say otherwise
+    fn get_charge(&self) -> Real {
+        0.0
+    }
+
+    /// Multiplies the force of gravity generators, e.g. balloons and sparks may fall slower
+    fn get_gravity_scale(&self) -> Real {
+        1.0
+    }
 }
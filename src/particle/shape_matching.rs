@@ -0,0 +1,512 @@
+use crate::matrix::Matrix3;
+use crate::particle::force_generator::gravity::Gravity;
+use crate::particle::force_generator::ForceGenerator;
+use crate::particle::particle_trait::ParticleTrait;
+use crate::particle::Particle;
+use crate::types::Real;
+use crate::vector::Vec3;
+use crate::GRAVITY;
+
+const QUADRATIC_SIZE: usize = 9;
+// the rotation of the previous frame is a good guess, so a few iterations are enough
+const POLAR_ITERATIONS: u32 = 8;
+
+type QuadraticMatrix = [[Real; QUADRATIC_SIZE]; QUADRATIC_SIZE];
+
+/// How far goal positions may depart from the rigidly rotated rest shape
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DeformationMode {
+    // the rest shape is only rotated and translated
+    Rigid,
+    // shear and stretch are allowed, the volume is preserved
+    Linear,
+    // twist and bend are allowed as well
+    Quadratic,
+}
+
+/// A group of particles matched to its rest shape independently of other clusters.
+/// Particles that belong to several clusters move to the average of their goals
+pub struct Cluster {
+    indices: Vec<usize>,
+    weights: Vec<Real>,
+    // rest positions relative to the rest center of mass
+    rest_offsets: Vec<Vec3>,
+    // (sum of m q q^T)^-1, None when the rest shape is degenerate (e.g. flat)
+    inverse_rest_moment: Option<Matrix3>,
+    inverse_quadratic_moment: Option<QuadraticMatrix>,
+    rotation: Matrix3,
+}
+
+impl Cluster {
+    fn new(particles: &[Particle], indices: Vec<usize>) -> Self {
+        let weights: Vec<Real> = indices.iter().map(|&i| weight(&particles[i])).collect();
+        let positions: Vec<Vec3> = indices
+            .iter()
+            .map(|&i| particles[i].get_position())
+            .collect();
+        let rest_center = center_of_mass(&positions, &weights);
+        let rest_offsets: Vec<Vec3> = positions.iter().map(|&p| p - rest_center).collect();
+
+        let mut rest_moment = Matrix3::new();
+        let mut quadratic_moment = [[0.0; QUADRATIC_SIZE]; QUADRATIC_SIZE];
+        for (q, &w) in rest_offsets.iter().zip(weights.iter()) {
+            rest_moment += &Matrix3::from_outer_product(q, q) * w;
+            let qq = quadratic_terms(q);
+            for (row, &a) in quadratic_moment.iter_mut().zip(qq.iter()) {
+                for (value, &b) in row.iter_mut().zip(qq.iter()) {
+                    *value += w * a * b;
+                }
+            }
+        }
+
+        Self {
+            indices,
+            weights,
+            rest_offsets,
+            inverse_rest_moment: rest_moment.get_inverse(),
+            inverse_quadratic_moment: invert_quadratic(quadratic_moment),
+            rotation: Matrix3::identity(),
+        }
+    }
+
+    /// Adds goal positions of the cluster particles to goals
+    fn add_goals(
+        &mut self,
+        particles: &[Particle],
+        mode: DeformationMode,
+        deformation: Real,
+        goals: &mut [Vec3],
+        goal_counts: &mut [u32],
+    ) {
+        let positions: Vec<Vec3> = self
+            .indices
+            .iter()
+            .map(|&i| particles[i].get_position())
+            .collect();
+        let center = center_of_mass(&positions, &self.weights);
+
+        let mut moment = Matrix3::new();
+        let mut quadratic_moment = [[0.0; QUADRATIC_SIZE]; 3];
+        for ((&x, q), &w) in positions
+            .iter()
+            .zip(self.rest_offsets.iter())
+            .zip(self.weights.iter())
+        {
+            let p = x - center;
+            moment += &Matrix3::from_outer_product(&p, q) * w;
+            if mode == DeformationMode::Quadratic {
+                let qq = quadratic_terms(q);
+                for (row, &a) in quadratic_moment.iter_mut().zip([p.x, p.y, p.z].iter()) {
+                    for (value, &b) in row.iter_mut().zip(qq.iter()) {
+                        *value += w * a * b;
+                    }
+                }
+            }
+        }
+        self.rotation = moment.get_polar_rotation(&self.rotation, POLAR_ITERATIONS);
+
+        match (
+            mode,
+            self.inverse_rest_moment,
+            self.inverse_quadratic_moment,
+        ) {
+            (DeformationMode::Linear, Some(inverse_rest_moment), _) => {
+                let mut linear = &moment * inverse_rest_moment;
+                let determinant = linear.get_determinant();
+                if determinant > 0.0 {
+                    // keeps the volume of the cluster
+                    linear = &linear * (1.0 / determinant.cbrt());
+                    let goal_transform =
+                        &(&linear * deformation) + &self.rotation * (1.0 - deformation);
+                    for (k, q) in self.rest_offsets.iter().enumerate() {
+                        let goal = center + goal_transform.transform(q);
+                        add_goal(self.indices[k], goal, goals, goal_counts);
+                    }
+                    return;
+                }
+            }
+            (DeformationMode::Quadratic, _, Some(inverse_quadratic_moment)) => {
+                let mut goal_transform = [[0.0; QUADRATIC_SIZE]; 3];
+                for (r, row) in goal_transform.iter_mut().enumerate() {
+                    for (c, value) in row.iter_mut().enumerate() {
+                        let quadratic: Real = (0..QUADRATIC_SIZE)
+                            .map(|k| quadratic_moment[r][k] * inverse_quadratic_moment[k][c])
+                            .sum();
+                        let rigid = if c < 3 {
+                            self.rotation.data[r * 3 + c]
+                        } else {
+                            0.0
+                        };
+                        *value = deformation * quadratic + (1.0 - deformation) * rigid;
+                    }
+                }
+                for (k, q) in self.rest_offsets.iter().enumerate() {
+                    let qq = quadratic_terms(q);
+                    let row = |r: usize| -> Real {
+                        goal_transform[r]
+                            .iter()
+                            .zip(qq.iter())
+                            .map(|(a, b)| a * b)
+                            .sum()
+                    };
+                    let goal = center + Vec3::from_values(row(0), row(1), row(2));
+                    add_goal(self.indices[k], goal, goals, goal_counts);
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        // rigid matching, also the fallback when the rest shape can't be deformed linearly
+        for (k, q) in self.rest_offsets.iter().enumerate() {
+            let goal = center + self.rotation.transform(q);
+            add_goal(self.indices[k], goal, goals, goal_counts);
+        }
+    }
+}
+
+/// Meshless deformable body, every step particles are pulled towards goal positions
+/// of their rest shape optimally rotated to match the current shape.
+/// See Müller et al., "Meshless Deformations Based on Shape Matching"
+pub struct ShapeMatchingBody {
+    particles: Vec<Particle>,
+    clusters: Vec<Cluster>,
+    // the part of the distance to the goal covered in one step, from 0 to 1
+    stiffness: Real,
+    // blends between the rigid (0) and the deformed (1) goal shape
+    deformation: Real,
+    mode: DeformationMode,
+}
+
+impl ShapeMatchingBody {
+    /// Creates a body with a single cluster containing all particles, the rest shape is given
+    pub fn new(positions: &[Vec3], particle_mass: Real) -> Self {
+        let clusters = vec![(0..positions.len()).collect()];
+        Self::new_clustered(positions, particle_mass, clusters)
+    }
+
+    /// Every cluster is a list of particle indices, clusters may overlap
+    pub fn new_clustered(
+        positions: &[Vec3],
+        particle_mass: Real,
+        clusters: Vec<Vec<usize>>,
+    ) -> Self {
+        let particles: Vec<Particle> = positions
+            .iter()
+            .map(|&position| {
+                let mut particle = Particle::new();
+                particle.set_mass(particle_mass);
+                particle.set_position(position);
+                particle
+            })
+            .collect();
+        let clusters = clusters
+            .into_iter()
+            .map(|indices| Cluster::new(&particles, indices))
+            .collect();
+        Self {
+            particles,
+            clusters,
+            stiffness: 0.5,
+            deformation: 0.0,
+            mode: DeformationMode::Rigid,
+        }
+    }
+
+    pub fn update(&mut self, duration: Real) {
+        if duration <= 0.0 {
+            return;
+        }
+        let mut gravity = Gravity::new(GRAVITY);
+        let mut goals = vec![Vec3::new(); self.particles.len()];
+        let mut goal_counts = vec![0; self.particles.len()];
+        for cluster in self.clusters.iter_mut() {
+            cluster.add_goals(
+                &self.particles,
+                self.mode,
+                self.deformation,
+                &mut goals,
+                &mut goal_counts,
+            );
+        }
+
+        for ((particle, goal), &count) in self
+            .particles
+            .iter_mut()
+            .zip(goals.iter())
+            .zip(goal_counts.iter())
+        {
+            if count > 0 {
+                let goal = goal * (1.0 / count as Real);
+                let mut velocity = particle.get_velocity();
+                velocity.add_scaled(&(goal - particle.get_position()), self.stiffness / duration);
+                particle.set_velocity(velocity);
+            }
+            gravity.update_force(particle, duration);
+            particle.integrate_symplectic(duration);
+        }
+    }
+
+    pub fn set_stiffness(&mut self, stiffness: Real) -> &mut Self {
+        self.stiffness = stiffness.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn set_deformation(&mut self, deformation: Real) -> &mut Self {
+        self.deformation = deformation.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn set_mode(&mut self, mode: DeformationMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn get_particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn get_particles_mut(&mut self) -> &mut [Particle] {
+        &mut self.particles
+    }
+
+    pub fn get_clusters(&self) -> &[Cluster] {
+        &self.clusters
+    }
+}
+
+// immovable particles still have to take part in matching
+fn weight(particle: &Particle) -> Real {
+    if particle.is_infinite_mass() {
+        return 1.0;
+    }
+    particle.get_mass()
+}
+
+fn center_of_mass(positions: &[Vec3], weights: &[Real]) -> Vec3 {
+    let mut center = Vec3::new();
+    let mut total_weight = 0.0;
+    for (p, &w) in positions.iter().zip(weights.iter()) {
+        center.add_scaled(p, w);
+        total_weight += w;
+    }
+    &center * (1.0 / total_weight)
+}
+
+fn add_goal(index: usize, goal: Vec3, goals: &mut [Vec3], goal_counts: &mut [u32]) {
+    goals[index] += goal;
+    goal_counts[index] += 1;
+}
+
+fn quadratic_terms(q: &Vec3) -> [Real; QUADRATIC_SIZE] {
+    [
+        q.x,
+        q.y,
+        q.z,
+        q.x * q.x,
+        q.y * q.y,
+        q.z * q.z,
+        q.x * q.y,
+        q.y * q.z,
+        q.z * q.x,
+    ]
+}
+
+/// Gauss-Jordan elimination with partial pivoting, None if the matrix is singular
+fn invert_quadratic(mut m: QuadraticMatrix) -> Option<QuadraticMatrix> {
+    let mut inverse = [[0.0; QUADRATIC_SIZE]; QUADRATIC_SIZE];
+    for (i, row) in inverse.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+    let scale = m
+        .iter()
+        .flat_map(|row| row.iter())
+        .fold(0.0, |max: Real, v| max.max(v.abs()));
+    for column in 0..QUADRATIC_SIZE {
+        let pivot = (column..QUADRATIC_SIZE)
+            .max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))
+            .unwrap();
+        if m[pivot][column].abs() <= scale * 1.0e-12 {
+            return None;
+        }
+        m.swap(column, pivot);
+        inverse.swap(column, pivot);
+        let divider = m[column][column];
+        for k in 0..QUADRATIC_SIZE {
+            m[column][k] /= divider;
+            inverse[column][k] /= divider;
+        }
+        for row in 0..QUADRATIC_SIZE {
+            if row == column {
+                continue;
+            }
+            let factor = m[row][column];
+            if factor == 0.0 {
+                continue;
+            }
+            for k in 0..QUADRATIC_SIZE {
+                m[row][k] -= factor * m[column][k];
+                inverse[row][k] -= factor * inverse[column][k];
+            }
+        }
+    }
+    Some(inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 3 x 3 x 3 grid of particles 0.5 apart
+    fn rest_shape() -> Vec<Vec3> {
+        let mut positions = Vec::new();
+        for i in 0..27 {
+            let coordinate = |n: usize| 0.5 * ((i / n) % 3) as Real - 0.5;
+            positions.push(Vec3::from_values(
+                coordinate(1),
+                coordinate(3),
+                coordinate(9),
+            ));
+        }
+        positions
+    }
+
+    fn body(clusters: Vec<Vec<usize>>, deform: impl Fn(&Vec3) -> Vec3) -> ShapeMatchingBody {
+        let rest = rest_shape();
+        let mut body = ShapeMatchingBody::new_clustered(&rest, 1.0, clusters);
+        for (particle, position) in body.get_particles_mut().iter_mut().zip(rest.iter()) {
+            particle.set_position(deform(position));
+            particle.set_gravity_scale(0.0).set_damping(0.01);
+        }
+        body
+    }
+
+    fn body_with(
+        mode: DeformationMode,
+        deformation: Real,
+        deform: impl Fn(&Vec3) -> Vec3,
+    ) -> ShapeMatchingBody {
+        let mut body = body(single_cluster(), deform);
+        body.set_mode(mode).set_deformation(deformation);
+        body
+    }
+
+    fn single_cluster() -> Vec<Vec<usize>> {
+        vec![(0..27).collect()]
+    }
+
+    // the largest difference of distances between particles from the rest shape
+    fn shape_error(body: &ShapeMatchingBody) -> Real {
+        let rest = rest_shape();
+        let particles = body.get_particles();
+        let mut error: Real = 0.0;
+        for i in 0..rest.len() {
+            for j in 0..i {
+                let distance = particles[i].get_position() - particles[j].get_position();
+                let rest_distance = (rest[i] - rest[j]).magnitude();
+                error = error.max((distance.magnitude() - rest_distance).abs());
+            }
+        }
+        error
+    }
+
+    fn simulate(body: &mut ShapeMatchingBody) {
+        for _ in 0..300 {
+            body.update(1.0 / 60.0);
+        }
+    }
+
+    fn squashed_and_rotated(position: &Vec3) -> Vec3 {
+        let rotation = Matrix3::from_axis_angle(&Vec3::from_values(1.0, 1.0, 0.0), 1.0);
+        rotation.transform(&Vec3::from_values(position.x, 0.5 * position.y, position.z))
+    }
+
+    fn bent(position: &Vec3) -> Vec3 {
+        Vec3::from_values(
+            position.x + 1.2 * position.y * position.y,
+            position.y,
+            position.z,
+        )
+    }
+
+    #[test]
+    fn rigid_body_returns_to_its_rest_shape() {
+        let mut body = body(single_cluster(), squashed_and_rotated);
+        assert!(shape_error(&body) > 0.1);
+        simulate(&mut body);
+        assert!(shape_error(&body) < 1.0e-3, "{}", shape_error(&body));
+    }
+
+    #[test]
+    fn linear_matching_keeps_the_volume() {
+        // uniform scaling has no shear to keep, the volume normalisation undoes it
+        let mut body = body(single_cluster(), |position| position * 2.0);
+        body.set_mode(DeformationMode::Linear).set_deformation(1.0);
+        simulate(&mut body);
+        assert!(shape_error(&body) < 1.0e-3, "{}", shape_error(&body));
+
+        let mut body = body_with(DeformationMode::Linear, 0.5, squashed_and_rotated);
+        simulate(&mut body);
+        assert!(shape_error(&body) < 1.0e-3, "{}", shape_error(&body));
+    }
+
+    #[test]
+    fn linear_matching_keeps_a_shear() {
+        let shear = |position: &Vec3| {
+            Vec3::from_values(position.x + 0.5 * position.y, position.y, position.z)
+        };
+        let mut body = body_with(DeformationMode::Linear, 1.0, shear);
+        let error = shape_error(&body);
+        simulate(&mut body);
+        assert!((shape_error(&body) - error).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn quadratic_matching_returns_a_bent_body() {
+        let mut body = body_with(DeformationMode::Quadratic, 0.5, bent);
+        assert!(shape_error(&body) > 0.1);
+        simulate(&mut body);
+        assert!(shape_error(&body) < 1.0e-3, "{}", shape_error(&body));
+
+        // the bend is a quadratic deformation, fully deformable goals mostly keep it,
+        // the terms have no constant one for the shift of the center the bend makes
+        let mut body = body_with(DeformationMode::Quadratic, 1.0, bent);
+        let error = shape_error(&body);
+        simulate(&mut body);
+        assert!((shape_error(&body) - error).abs() < 0.05 * error);
+    }
+
+    #[test]
+    fn overlapping_clusters_average_their_goals() {
+        let clusters = vec![(0..18).collect(), (9..27).collect()];
+        let mut body = body(clusters, squashed_and_rotated);
+        assert_eq!(body.get_clusters().len(), 2);
+        simulate(&mut body);
+        assert!(shape_error(&body) < 1.0e-3, "{}", shape_error(&body));
+    }
+
+    #[test]
+    fn quadratic_moment_inverse() {
+        let mut m = [[0.0; QUADRATIC_SIZE]; QUADRATIC_SIZE];
+        for (r, row) in m.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = 1.0 / (1 + r + c) as Real + if r == c { 1.0 } else { 0.0 };
+            }
+        }
+        let inverse = invert_quadratic(m).unwrap();
+        for (r, row) in m.iter().enumerate() {
+            for c in 0..QUADRATIC_SIZE {
+                let product: Real = row
+                    .iter()
+                    .zip(inverse.iter())
+                    .map(|(a, inverse_row)| a * inverse_row[c])
+                    .sum();
+                let identity = if r == c { 1.0 } else { 0.0 };
+                assert!((product - identity).abs() < 1.0e-9);
+            }
+        }
+
+        m[4] = m[2];
+        assert!(invert_quadratic(m).is_none());
+    }
+}
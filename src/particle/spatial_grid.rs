@@ -0,0 +1,69 @@
+use crate::types::Real;
+use crate::vector::Vec3;
+use std::collections::HashMap;
+
+type Cell = (i64, i64, i64);
+
+/// Uniform hash grid for neighbour search between particles. The cell size should be
+/// at least the search radius, then all neighbours are in the 27 surrounding cells
+pub struct SpatialGrid {
+    cell_size: Real,
+    cells: HashMap<Cell, Vec<usize>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: Real) -> Self {
+        if cell_size <= 0.0 {
+            panic!("Cell size should be greater then 0");
+        }
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Forgets all indices, but keeps allocated cells to avoid allocations next frame
+    pub fn clear(&mut self) {
+        self.cells.values_mut().for_each(|cell| cell.clear());
+    }
+
+    pub fn insert(&mut self, index: usize, position: &Vec3) {
+        let cell = self.get_cell(position);
+        self.cells.entry(cell).or_default().push(index);
+    }
+
+    /// Clears the grid and inserts all positions, indices are positions in the iterator
+    pub fn rebuild<'a, I: Iterator<Item = &'a Vec3>>(&mut self, positions: I) {
+        self.clear();
+        for (index, position) in positions.enumerate() {
+            self.insert(index, position);
+        }
+    }
+
+    /// Calls f for all indices in the cell of the position and in the surrounding cells.
+    /// These are candidates only, the caller has to check the actual distance
+    pub fn for_each_candidate<F: FnMut(usize)>(&self, position: &Vec3, mut f: F) {
+        let (x, y, z) = self.get_cell(position);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(cell) = self.cells.get(&(x + dx, y + dy, z + dz)) {
+                        cell.iter().for_each(|&index| f(index));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn get_cell_size(&self) -> Real {
+        self.cell_size
+    }
+
+    fn get_cell(&self, position: &Vec3) -> Cell {
+        (
+            (position.x / self.cell_size).floor() as i64,
+            (position.y / self.cell_size).floor() as i64,
+            (position.z / self.cell_size).floor() as i64,
+        )
+    }
+}
@@ -9,7 +9,7 @@ use std::marker::PhantomData;
 struct World<'a, P, C, FR, FG>
 where
     P: ParticleTrait,
-    C: ContactGenerator<'a, P>,
+    C: ContactGenerator<P>,
     FR: ForceRegistry<P, FG>,
     FG: ForceGenerator,
 {
@@ -26,7 +26,7 @@ where
 impl<'a, P, C, FR, FG> World<'a, P, C, FR, FG>
 where
     P: ParticleTrait,
-    C: ContactGenerator<'a, P>,
+    C: ContactGenerator<P>,
     FR: ForceRegistry<P, FG>,
     FG: ForceGenerator,
 {
@@ -0,0 +1,294 @@
+use crate::particle::xpbd::project;
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// A positional constraint between particles of the solver, particles are referenced by index.
+/// Compliance is the inverse stiffness, zero makes the constraint infinitely stiff
+pub trait Constraint {
+    /// Moves positions towards satisfying the constraint, duration is the substep length
+    fn solve(&mut self, positions: &mut [Vec3], inverse_masses: &[Real], duration: Real);
+}
+
+/// Keeps two particles at the rest distance, used for cloth, ropes and rods
+pub struct DistanceConstraint {
+    pub particles: (usize, usize),
+    pub rest_length: Real,
+    pub compliance: Real,
+}
+
+impl DistanceConstraint {
+    pub fn new(particles: (usize, usize), rest_length: Real, compliance: Real) -> Self {
+        Self {
+            particles,
+            rest_length,
+            compliance,
+        }
+    }
+}
+
+impl Constraint for DistanceConstraint {
+    fn solve(&mut self, positions: &mut [Vec3], inverse_masses: &[Real], duration: Real) {
+        let (a, b) = self.particles;
+        let mut normal = positions[a] - positions[b];
+        let length = normal.magnitude();
+        if length == 0.0 {
+            return;
+        }
+        normal.normalize();
+        project(
+            positions,
+            inverse_masses,
+            &[a, b],
+            &[normal, &normal * -1.0],
+            length - self.rest_length,
+            self.compliance,
+            duration,
+        );
+    }
+}
+
+/// Keeps the dihedral angle between two triangles sharing an edge at its rest value.
+/// The first triangle is (wings.0, edge.0, edge.1), the second one is (wings.1, edge.1, edge.0)
+pub struct BendingConstraint {
+    pub wings: (usize, usize),
+    pub edge: (usize, usize),
+    // zero for flat triangles
+    pub rest_angle: Real,
+    pub compliance: Real,
+}
+
+impl BendingConstraint {
+    pub fn new(
+        wings: (usize, usize),
+        edge: (usize, usize),
+        rest_angle: Real,
+        compliance: Real,
+    ) -> Self {
+        Self {
+            wings,
+            edge,
+            rest_angle,
+            compliance,
+        }
+    }
+}
+
+impl Constraint for BendingConstraint {
+    fn solve(&mut self, positions: &mut [Vec3], inverse_masses: &[Real], duration: Real) {
+        let indices = [self.wings.0, self.wings.1, self.edge.0, self.edge.1];
+        let points = [
+            positions[indices[0]],
+            positions[indices[1]],
+            positions[indices[2]],
+            positions[indices[3]],
+        ];
+        if let Some((angle, gradients)) = dihedral_angle(&points) {
+            let mut difference = angle - self.rest_angle;
+            // take the short way around
+            let pi = std::f64::consts::PI as Real;
+            if difference > pi {
+                difference -= 2.0 * pi;
+            } else if difference < -pi {
+                difference += 2.0 * pi;
+            }
+            project(
+                positions,
+                inverse_masses,
+                &indices,
+                &gradients,
+                difference,
+                self.compliance,
+                duration,
+            );
+        }
+    }
+}
+
+/// Keeps the angle at the middle particle between segments to the other two at its rest
+/// value, so ropes and hair made of particles bend stiffly instead of folding like chains
+pub struct AngleConstraint {
+    // the angle is at the middle one
+    pub particles: [usize; 3],
+    // pi for a straight line
+    pub rest_angle: Real,
+    pub compliance: Real,
+}
+
+impl AngleConstraint {
+    pub fn new(particles: [usize; 3], rest_angle: Real, compliance: Real) -> Self {
+        Self {
+            particles,
+            rest_angle,
+            compliance,
+        }
+    }
+}
+
+impl Constraint for AngleConstraint {
+    fn solve(&mut self, positions: &mut [Vec3], inverse_masses: &[Real], duration: Real) {
+        let points = [
+            positions[self.particles[0]],
+            positions[self.particles[1]],
+            positions[self.particles[2]],
+        ];
+        if let Some((angle, gradients)) = bend_angle(&points) {
+            project(
+                positions,
+                inverse_masses,
+                &self.particles,
+                &gradients,
+                angle - self.rest_angle,
+                self.compliance,
+                duration,
+            );
+        }
+    }
+}
+
+/// Keeps the volume of a tetrahedron, the building block of volumetric soft bodies
+pub struct VolumeConstraint {
+    pub particles: [usize; 4],
+    pub rest_volume: Real,
+    pub compliance: Real,
+}
+
+impl VolumeConstraint {
+    pub fn new(particles: [usize; 4], rest_volume: Real, compliance: Real) -> Self {
+        Self {
+            particles,
+            rest_volume,
+            compliance,
+        }
+    }
+}
+
+impl Constraint for VolumeConstraint {
+    fn solve(&mut self, positions: &mut [Vec3], inverse_masses: &[Real], duration: Real) {
+        let [i0, i1, i2, i3] = self.particles;
+        let (x0, x1, x2, x3) = (positions[i0], positions[i1], positions[i2], positions[i3]);
+        let gradients = [
+            (x3 - x1) % (x2 - x1),
+            (x2 - x0) % (x3 - x0),
+            (x3 - x0) % (x1 - x0),
+            (x1 - x0) % (x2 - x0),
+        ];
+        let volume = tetrahedron_volume(&x0, &x1, &x2, &x3);
+        // the gradients are of six times the volume
+        project(
+            positions,
+            inverse_masses,
+            &self.particles,
+            &gradients,
+            6.0 * (volume - self.rest_volume),
+            self.compliance,
+            duration,
+        );
+    }
+}
+
+/// Ties a particle to a point in the world, with zero compliance the particle is pinned
+pub struct AttachmentConstraint {
+    pub particle: usize,
+    pub target: Vec3,
+    pub compliance: Real,
+}
+
+impl AttachmentConstraint {
+    pub fn new(particle: usize, target: Vec3, compliance: Real) -> Self {
+        Self {
+            particle,
+            target,
+            compliance,
+        }
+    }
+}
+
+impl Constraint for AttachmentConstraint {
+    fn solve(&mut self, positions: &mut [Vec3], inverse_masses: &[Real], duration: Real) {
+        let mut normal = positions[self.particle] - self.target;
+        let distance = normal.magnitude();
+        if distance == 0.0 {
+            return;
+        }
+        normal.normalize();
+        project(
+            positions,
+            inverse_masses,
+            &[self.particle],
+            &[normal],
+            distance,
+            self.compliance,
+            duration,
+        );
+    }
+}
+
+pub fn tetrahedron_volume(x0: &Vec3, x1: &Vec3, x2: &Vec3, x3: &Vec3) -> Real {
+    &((*x1 - *x0) % (*x2 - *x0)) * &(*x3 - *x0) / 6.0
+}
+
+/// The signed dihedral angle between triangles (p0, p2, p3) and (p1, p3, p2) and its
+/// gradients with respect to the four points, zero when the triangles are flat.
+/// The gradients are Bridson's, see "Simulation of Clothing with Folds and Wrinkles".
+/// Returns None for degenerate triangles
+pub fn dihedral_angle(points: &[Vec3; 4]) -> Option<(Real, [Vec3; 4])> {
+    let [p0, p1, p2, p3] = *points;
+    let edge = p3 - p2;
+    let edge_length = edge.magnitude();
+    let n1 = (p0 - p2) % (p0 - p3);
+    let n2 = (p1 - p3) % (p1 - p2);
+    let (n1_square, n2_square) = (n1.square_magnitude(), n2.square_magnitude());
+    if edge_length == 0.0 || n1_square == 0.0 || n2_square == 0.0 {
+        return None;
+    }
+
+    let mut n1_unit = n1;
+    n1_unit.normalize();
+    let mut n2_unit = n2;
+    n2_unit.normalize();
+    let sin = &(n1_unit % n2_unit) * &edge / edge_length;
+    let cos = &n1_unit * &n2_unit;
+    let angle = sin.atan2(cos);
+
+    let a1 = &n1 * (1.0 / n1_square);
+    let a2 = &n2 * (1.0 / n2_square);
+    let gradients = [
+        &a1 * -edge_length,
+        &a2 * -edge_length,
+        &a1 * -(&(p0 - p3) * &edge / edge_length) + &a2 * -(&(p1 - p3) * &edge / edge_length),
+        &a1 * (&(p0 - p2) * &edge / edge_length) + &a2 * (&(p1 - p2) * &edge / edge_length),
+    ];
+    Some((angle, gradients))
+}
+
+/// The angle at p1 between segments to p0 and p2, from 0 folded to pi straight, and its
+/// gradients with respect to the three points. Returns None for zero length segments and
+/// for exactly straight or folded ones, where the direction of bending is undefined
+pub fn bend_angle(points: &[Vec3; 3]) -> Option<(Real, [Vec3; 3])> {
+    let [p0, p1, p2] = *points;
+    let (u, v) = (p0 - p1, p2 - p1);
+    let (u_length, v_length) = (u.magnitude(), v.magnitude());
+    if u_length == 0.0 || v_length == 0.0 {
+        return None;
+    }
+    let angle = (u % v).magnitude().atan2(&u * &v);
+
+    let mut u_unit = u;
+    u_unit.normalize();
+    let mut v_unit = v;
+    v_unit.normalize();
+    let cos = &u_unit * &v_unit;
+    // directions in the bending plane perpendicular to each segment, towards the other one
+    let mut u_normal = v_unit - &u_unit * cos;
+    let mut v_normal = u_unit - &v_unit * cos;
+    if u_normal.square_magnitude() == 0.0 || v_normal.square_magnitude() == 0.0 {
+        return None;
+    }
+    u_normal.normalize();
+    v_normal.normalize();
+
+    let g0 = &u_normal * (-1.0 / u_length);
+    let g2 = &v_normal * (-1.0 / v_length);
+    let g1 = &(g0 + g2) * -1.0;
+    Some((angle, [g0, g1, g2]))
+}
@@ -0,0 +1,514 @@
+pub mod constraint;
+
+use crate::particle::particle_trait::ParticleTrait;
+use crate::particle::spatial_grid::SpatialGrid;
+use crate::particle::xpbd::constraint::{
+    tetrahedron_volume, AngleConstraint, AttachmentConstraint, BendingConstraint, Constraint,
+    DistanceConstraint, VolumeConstraint,
+};
+use crate::particle::Particle;
+use crate::plane::Plane;
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// Extended position based dynamics solver. Instead of accumulating forces and resolving
+/// impulses, it predicts particle positions, moves them to satisfy constraints and derives
+/// velocities from the position change. Every step is split into substeps with a single
+/// constraint iteration each, which converges better than many iterations per step.
+/// See Macklin et al., "Small Steps in Physics Simulation"
+pub struct XpbdSolver {
+    particles: Vec<Particle>,
+    constraints: Vec<Box<dyn Constraint>>,
+    attachments: Vec<AttachmentConstraint>,
+    collision_planes: Vec<Plane>,
+    substeps: u32,
+    // particles collide as spheres of this radius, with planes and with each other
+    particle_radius: Real,
+    self_collision: bool,
+    // coulomb friction coefficient for collisions
+    friction: Real,
+    grid: SpatialGrid,
+}
+
+impl XpbdSolver {
+    pub fn new(particles: Vec<Particle>, substeps: u32) -> Self {
+        Self {
+            particles,
+            constraints: Vec::new(),
+            attachments: Vec::new(),
+            collision_planes: Vec::new(),
+            substeps: substeps.max(1),
+            particle_radius: 0.0,
+            self_collision: false,
+            friction: 0.0,
+            grid: SpatialGrid::new(1.0),
+        }
+    }
+
+    pub fn add_constraint(&mut self, constraint: Box<dyn Constraint>) {
+        self.constraints.push(constraint);
+    }
+
+    /// The rest length is the current distance between the particles
+    pub fn add_distance_constraint(&mut self, a: usize, b: usize, compliance: Real) {
+        let rest_length = (self.get_position(a) - self.get_position(b)).magnitude();
+        self.add_constraint(Box::new(DistanceConstraint::new(
+            (a, b),
+            rest_length,
+            compliance,
+        )));
+    }
+
+    /// The rest angle is the current angle between triangles (wings.0, edge.0, edge.1)
+    /// and (wings.1, edge.1, edge.0)
+    pub fn add_bending_constraint(
+        &mut self,
+        wings: (usize, usize),
+        edge: (usize, usize),
+        compliance: Real,
+    ) {
+        let points = [
+            self.get_position(wings.0),
+            self.get_position(wings.1),
+            self.get_position(edge.0),
+            self.get_position(edge.1),
+        ];
+        let rest_angle = constraint::dihedral_angle(&points).map_or(0.0, |(angle, _)| angle);
+        self.add_constraint(Box::new(BendingConstraint::new(
+            wings, edge, rest_angle, compliance,
+        )));
+    }
+
+    /// The rest angle is the current angle at particles[1]
+    pub fn add_angle_constraint(&mut self, particles: [usize; 3], compliance: Real) {
+        let u = self.get_position(particles[0]) - self.get_position(particles[1]);
+        let v = self.get_position(particles[2]) - self.get_position(particles[1]);
+        let rest_angle = (u % v).magnitude().atan2(&u * &v);
+        self.add_constraint(Box::new(AngleConstraint::new(
+            particles, rest_angle, compliance,
+        )));
+    }
+
+    /// The rest volume is the current volume of the tetrahedron
+    pub fn add_volume_constraint(&mut self, particles: [usize; 4], compliance: Real) {
+        let rest_volume = tetrahedron_volume(
+            &self.get_position(particles[0]),
+            &self.get_position(particles[1]),
+            &self.get_position(particles[2]),
+            &self.get_position(particles[3]),
+        );
+        self.add_constraint(Box::new(VolumeConstraint::new(
+            particles,
+            rest_volume,
+            compliance,
+        )));
+    }
+
+    /// Returns the attachment index to move its target later
+    pub fn add_attachment(&mut self, particle: usize, target: Vec3, compliance: Real) -> usize {
+        self.attachments
+            .push(AttachmentConstraint::new(particle, target, compliance));
+        self.attachments.len() - 1
+    }
+
+    pub fn set_attachment_target(&mut self, attachment: usize, target: Vec3) {
+        self.attachments[attachment].target = target;
+    }
+
+    pub fn remove_attachment(&mut self, attachment: usize) -> AttachmentConstraint {
+        self.attachments.remove(attachment)
+    }
+
+    pub fn add_collision_plane(&mut self, plane: Plane) {
+        self.collision_planes.push(plane);
+    }
+
+    pub fn set_particle_radius(&mut self, radius: Real) -> &mut Self {
+        self.particle_radius = radius;
+        if radius > 0.0 {
+            self.grid = SpatialGrid::new(4.0 * radius);
+        }
+        self
+    }
+
+    pub fn set_self_collision(&mut self, self_collision: bool) -> &mut Self {
+        self.self_collision = self_collision;
+        self
+    }
+
+    pub fn set_friction(&mut self, friction: Real) -> &mut Self {
+        self.friction = friction;
+        self
+    }
+
+    pub fn set_substeps(&mut self, substeps: u32) -> &mut Self {
+        self.substeps = substeps.max(1);
+        self
+    }
+
+    pub fn run_physics(&mut self, duration: Real) {
+        if duration <= 0.0 {
+            return;
+        }
+        let substep = duration / self.substeps as Real;
+        let inverse_masses: Vec<Real> = self
+            .particles
+            .iter()
+            .map(|p| p.get_inverse_mass())
+            .collect();
+        let mut positions: Vec<Vec3> = self.particles.iter().map(|p| p.get_position()).collect();
+        let mut previous_positions = positions.clone();
+        let collision_pairs = self.find_collision_pairs(&positions, duration);
+
+        for _ in 0..self.substeps {
+            for (i, particle) in self.particles.iter_mut().enumerate() {
+                previous_positions[i] = positions[i];
+                if particle.is_infinite_mass() {
+                    continue;
+                }
+                let mut acceleration = particle.get_acceleration();
+                acceleration.add_scaled(&particle.get_force_accum(), inverse_masses[i]);
+                let mut velocity = particle.get_velocity();
+                velocity.add_scaled(&acceleration, substep);
+                particle.set_velocity(velocity);
+                positions[i].add_scaled(&velocity, substep);
+            }
+
+            for constraint in self.constraints.iter_mut() {
+                constraint.solve(&mut positions, &inverse_masses, substep);
+            }
+            for attachment in self.attachments.iter_mut() {
+                attachment.solve(&mut positions, &inverse_masses, substep);
+            }
+            self.solve_collisions(
+                &mut positions,
+                &previous_positions,
+                &inverse_masses,
+                &collision_pairs,
+            );
+
+            for (i, particle) in self.particles.iter_mut().enumerate() {
+                if particle.is_infinite_mass() {
+                    continue;
+                }
+                let mut velocity = &(positions[i] - previous_positions[i]) * (1.0 / substep);
+                velocity *= particle.get_damping().powf(substep);
+                particle.set_velocity(velocity);
+            }
+        }
+
+        for (particle, position) in self.particles.iter_mut().zip(positions.iter()) {
+            particle.set_position(*position);
+            particle.clear_accumulator();
+        }
+    }
+
+    /// Pairs of particles that may touch during the step, found once per step
+    fn find_collision_pairs(&mut self, positions: &[Vec3], duration: Real) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        if !self.self_collision || self.particle_radius <= 0.0 {
+            return pairs;
+        }
+        let max_speed = self
+            .particles
+            .iter()
+            .map(|p| p.get_velocity_magnitude())
+            .fold(0.0, Real::max);
+        let search_distance = 2.0 * self.particle_radius + 2.0 * max_speed * duration;
+        if search_distance > self.grid.get_cell_size() {
+            self.grid = SpatialGrid::new(search_distance);
+        }
+        self.grid.rebuild(positions.iter());
+        for (i, position) in positions.iter().enumerate() {
+            self.grid.for_each_candidate(position, |j| {
+                if j > i && (positions[j] - *position).magnitude() < search_distance {
+                    pairs.push((i, j));
+                }
+            });
+        }
+        pairs
+    }
+
+    fn solve_collisions(
+        &self,
+        positions: &mut [Vec3],
+        previous_positions: &[Vec3],
+        inverse_masses: &[Real],
+        collision_pairs: &[(usize, usize)],
+    ) {
+        for plane in self.collision_planes.iter() {
+            for i in 0..positions.len() {
+                let penetration = self.particle_radius - plane.distance(&positions[i]);
+                if penetration <= 0.0 || inverse_masses[i] == 0.0 {
+                    continue;
+                }
+                positions[i].add_scaled(&plane.normal, penetration);
+                // static friction keeps the particle in place while the tangential
+                // movement is within the friction cone, it's scaled down otherwise
+                let movement = positions[i] - previous_positions[i];
+                let tangential = movement - &plane.normal * (&movement * &plane.normal);
+                let tangential_length = tangential.magnitude();
+                if tangential_length <= 0.0 {
+                    continue;
+                }
+                let allowed = self.friction * penetration;
+                let correction = if tangential_length <= allowed {
+                    1.0
+                } else {
+                    allowed / tangential_length
+                };
+                positions[i].add_scaled(&tangential, -correction);
+            }
+        }
+
+        let minimal_distance = 2.0 * self.particle_radius;
+        for &(a, b) in collision_pairs {
+            let mut normal = positions[a] - positions[b];
+            let distance = normal.magnitude();
+            if distance >= minimal_distance || distance == 0.0 {
+                continue;
+            }
+            normal.normalize();
+            project(
+                positions,
+                inverse_masses,
+                &[a, b],
+                &[normal, &normal * -1.0],
+                distance - minimal_distance,
+                0.0,
+                1.0,
+            );
+        }
+    }
+
+    pub fn get_position(&self, index: usize) -> Vec3 {
+        self.particles[index].get_position()
+    }
+
+    pub fn get_particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn get_particles_mut(&mut self) -> &mut [Particle] {
+        &mut self.particles
+    }
+}
+
+/// A single XPBD projection of the constraint C = value with the given gradients.
+/// The lagrange multiplier starts from zero every substep, so it isn't stored
+pub(crate) fn project(
+    positions: &mut [Vec3],
+    inverse_masses: &[Real],
+    indices: &[usize],
+    gradients: &[Vec3],
+    value: Real,
+    compliance: Real,
+    duration: Real,
+) {
+    let alpha = compliance / (duration * duration);
+    let weight: Real = indices
+        .iter()
+        .zip(gradients.iter())
+        .map(|(&i, gradient)| inverse_masses[i] * gradient.square_magnitude())
+        .sum();
+    if weight + alpha <= 0.0 {
+        return;
+    }
+    let lambda = -value / (weight + alpha);
+    for (&i, gradient) in indices.iter().zip(gradients.iter()) {
+        positions[i].add_scaled(gradient, lambda * inverse_masses[i]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GRAVITY;
+
+    fn particle(position: Vec3, damping: Real) -> Particle {
+        let mut particle = Particle::new();
+        particle.set_damping(damping).add_acceleration(GRAVITY);
+        particle.set_position(position);
+        particle
+    }
+
+    #[test]
+    fn pinned_rope_keeps_its_length() {
+        let mut particles: Vec<Particle> = (0..10)
+            .map(|i| particle(Vec3::from_values(0.1 * i as Real, 0.0, 0.0), 0.999))
+            .collect();
+        particles[0].set_inverse_mass(0.0);
+        let mut solver = XpbdSolver::new(particles, 20);
+        for i in 0..9 {
+            solver.add_distance_constraint(i, i + 1, 0.0);
+        }
+        for _ in 0..120 {
+            solver.run_physics(1.0 / 60.0);
+            for i in 0..9 {
+                let length = (solver.get_position(i) - solver.get_position(i + 1)).magnitude();
+                assert!((length - 0.1).abs() < 1.0e-3, "{}", length);
+            }
+        }
+        assert_eq!(solver.get_position(0).magnitude(), 0.0);
+        // it swings down
+        assert!(solver.get_position(9).y < -0.1);
+    }
+
+    // a particle of 1 kg hanging from a pinned one 1 m above, after it settles
+    fn hanging_length(compliance: Real) -> Real {
+        let mut particles = vec![
+            particle(Vec3::from_values(0.0, 1.0, 0.0), 0.01),
+            particle(Vec3::new(), 0.01),
+        ];
+        particles[0].set_inverse_mass(0.0);
+        let mut solver = XpbdSolver::new(particles, 10);
+        solver.add_distance_constraint(0, 1, compliance);
+        for _ in 0..600 {
+            solver.run_physics(1.0 / 60.0);
+        }
+        (solver.get_position(0) - solver.get_position(1)).magnitude()
+    }
+
+    #[test]
+    fn compliance_is_the_inverse_stiffness() {
+        // zero compliance is infinitely stiff
+        assert!((hanging_length(0.0) - 1.0).abs() < 1.0e-6);
+        // a spring of stiffness 1 / compliance stretches by m * g * compliance
+        let compliance = 1.0e-3;
+        let stretch = GRAVITY.magnitude() * compliance;
+        assert!((hanging_length(compliance) - (1.0 + stretch)).abs() < 1.0e-2 * stretch);
+    }
+
+    #[test]
+    fn particles_rest_on_a_collision_plane() {
+        let particles = vec![particle(Vec3::from_values(0.0, 1.0, 0.0), 0.999)];
+        let mut solver = XpbdSolver::new(particles, 4);
+        solver
+            .set_particle_radius(0.1)
+            .add_collision_plane(Plane::new(Vec3::from_values(0.0, 2.0, 0.0), 0.0));
+        for _ in 0..120 {
+            solver.run_physics(1.0 / 60.0);
+        }
+        assert!((solver.get_position(0).y - 0.1).abs() < 1.0e-6);
+    }
+
+    // a rod of three particles sticking out of a wall, the first two are fixed
+    fn rod_end_height(stiff: bool) -> Real {
+        let mut particles: Vec<Particle> = (0..3)
+            .map(|i| particle(Vec3::from_values(0.5 * i as Real, 0.0, 0.0), 0.99))
+            .collect();
+        particles[0].set_inverse_mass(0.0);
+        particles[1].set_inverse_mass(0.0);
+        let mut solver = XpbdSolver::new(particles, 10);
+        solver.add_distance_constraint(1, 2, 0.0);
+        if stiff {
+            solver.add_angle_constraint([0, 1, 2], 0.0);
+        }
+        for _ in 0..120 {
+            solver.run_physics(1.0 / 60.0);
+        }
+        solver.get_position(2).y
+    }
+
+    #[test]
+    fn angle_constraint_keeps_a_rod_straight() {
+        assert!(rod_end_height(true).abs() < 1.0e-3);
+        // without it the end hangs down
+        assert!(rod_end_height(false) < -0.4);
+    }
+
+    fn floating(positions: &[Vec3]) -> XpbdSolver {
+        let particles = positions
+            .iter()
+            .map(|&position| {
+                let mut particle = Particle::new();
+                particle.set_damping(0.9).set_position(position);
+                particle
+            })
+            .collect();
+        XpbdSolver::new(particles, 10)
+    }
+
+    fn run(solver: &mut XpbdSolver, steps: u32) {
+        for _ in 0..steps {
+            solver.run_physics(1.0 / 60.0);
+        }
+    }
+
+    #[test]
+    fn folded_triangles_flatten() {
+        let mut solver = floating(&[
+            Vec3::from_values(0.5, 0.0, 1.0),
+            Vec3::from_values(0.5, 0.0, -1.0),
+            Vec3::new(),
+            Vec3::from_values(1.0, 0.0, 0.0),
+        ]);
+        for &(a, b) in [(0, 2), (0, 3), (1, 2), (1, 3), (2, 3)].iter() {
+            solver.add_distance_constraint(a, b, 0.0);
+        }
+        solver.add_bending_constraint((0, 1), (2, 3), 0.0);
+        // the second triangle is folded up around the shared edge
+        solver.get_particles_mut()[1].set_position(Vec3::from_values(0.5, 1.0, 0.0));
+        let angle = |solver: &XpbdSolver| {
+            let points = [0, 1, 2, 3].map(|i| solver.get_position(i));
+            constraint::dihedral_angle(&points).unwrap().0
+        };
+        assert!(angle(&solver).abs() > 1.0);
+        run(&mut solver, 120);
+        assert!(angle(&solver).abs() < 1.0e-3, "{}", angle(&solver));
+    }
+
+    #[test]
+    fn squashed_tetrahedron_regains_its_volume() {
+        let mut solver = floating(&[
+            Vec3::new(),
+            Vec3::from_values(1.0, 0.0, 0.0),
+            Vec3::from_values(0.0, 0.0, 1.0),
+            Vec3::from_values(0.0, 1.0, 0.0),
+        ]);
+        solver.add_volume_constraint([0, 1, 2, 3], 0.0);
+        let volume = |solver: &XpbdSolver| {
+            let [x0, x1, x2, x3] = [0, 1, 2, 3].map(|i| solver.get_position(i));
+            tetrahedron_volume(&x0, &x1, &x2, &x3)
+        };
+        let rest_volume = volume(&solver);
+        solver.get_particles_mut()[3].set_position(Vec3::from_values(0.0, 0.2, 0.0));
+        run(&mut solver, 60);
+        assert!((volume(&solver) - rest_volume).abs() < 1.0e-3 * rest_volume.abs());
+    }
+
+    #[test]
+    fn attached_particle_follows_its_target() {
+        let mut solver = XpbdSolver::new(vec![particle(Vec3::new(), 0.99)], 10);
+        let attachment = solver.add_attachment(0, Vec3::new(), 0.0);
+        for step in 0..60 {
+            let target = Vec3::from_values(0.05 * step as Real, 1.0, 0.0);
+            solver.set_attachment_target(attachment, target);
+            solver.run_physics(1.0 / 60.0);
+            // gravity pulls it off a little every substep
+            assert!((solver.get_position(0) - target).magnitude() < 1.0e-3);
+        }
+
+        solver.remove_attachment(attachment);
+        run(&mut solver, 30);
+        assert!(solver.get_position(0).y < 0.0);
+    }
+
+    #[test]
+    fn colliding_particles_dont_overlap() {
+        let mut solver = floating(&[
+            Vec3::from_values(-0.5, 0.0, 0.0),
+            Vec3::from_values(0.5, 0.0, 0.0),
+        ]);
+        solver.set_particle_radius(0.1).set_self_collision(true);
+        solver.get_particles_mut()[0].set_velocity(Vec3::from_values(3.0, 0.0, 0.0));
+        solver.get_particles_mut()[1].set_velocity(Vec3::from_values(-3.0, 0.0, 0.0));
+        for _ in 0..60 {
+            solver.run_physics(1.0 / 60.0);
+            let distance = (solver.get_position(0) - solver.get_position(1)).magnitude();
+            assert!(distance > 0.2 - 1.0e-9, "{}", distance);
+        }
+        // they don't pass through each other
+        assert!(solver.get_position(0).x < solver.get_position(1).x);
+    }
+}
@@ -0,0 +1,46 @@
+use crate::types::Real;
+use crate::vector::Vec3;
+
+/// Infinite plane, the set of points p for which normal * p = offset.
+/// The normal points to the free (outer) side
+#[derive(Copy, Clone, Debug)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub offset: Real,
+}
+
+impl Plane {
+    /// The normal doesn't have to be normalized
+    pub fn new(mut normal: Vec3, offset: Real) -> Self {
+        let magnitude = normal.magnitude();
+        if magnitude <= 0.0 {
+            panic!("Length of the plane normal should be greater then 0");
+        }
+        normal.normalize();
+        Plane {
+            normal,
+            offset: offset / magnitude,
+        }
+    }
+
+    pub fn from_point(mut normal: Vec3, point: Vec3) -> Self {
+        if normal.magnitude() <= 0.0 {
+            panic!("Length of the plane normal should be greater then 0");
+        }
+        normal.normalize();
+        Plane {
+            normal,
+            offset: &normal * &point,
+        }
+    }
+
+    /// Signed distance from the plane, negative behind it
+    pub fn distance(&self, point: &Vec3) -> Real {
+        &self.normal * point - self.offset
+    }
+
+    /// The closest point lying on the plane
+    pub fn project(&self, point: &Vec3) -> Vec3 {
+        *point - &self.normal * self.distance(point)
+    }
+}
@@ -2,6 +2,7 @@ use crate::types::Real;
 use crate::vector::Vec3;
 use std::ops;
 
+#[derive(Copy, Clone, Debug)]
 pub struct Quaternion {
     pub r: Real,
     pub i: Real,
@@ -11,6 +12,21 @@ pub struct Quaternion {
 }
 
 impl Quaternion {
+    pub fn new(r: Real, i: Real, j: Real, k: Real) -> Self {
+        Quaternion {
+            r,
+            i,
+            j,
+            k,
+            data: [0.0; 4],
+        }
+    }
+
+    /// The quaternion of no rotation
+    pub fn identity() -> Self {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
     // normalized quaternion is q = q / |q|
     pub fn normalize(&self) -> Quaternion {
         let n = (self.r * self.r + self.i * self.i + self.j * self.j + self.k * self.k).sqrt();
@@ -53,10 +69,10 @@ impl Quaternion {
             data: [0.0; 4],
         };
         q *= self;
-        self.r = q.r * 0.5;
-        self.i = q.i * 0.5;
-        self.r = q.j * 0.5;
-        self.r = q.k * 0.5;
+        self.r += q.r * 0.5;
+        self.i += q.i * 0.5;
+        self.j += q.j * 0.5;
+        self.k += q.k * 0.5;
     }
 }
 
@@ -78,9 +94,45 @@ impl ops::Mul<&Quaternion> for &Quaternion {
 
 impl ops::MulAssign<&Quaternion> for Quaternion {
     fn mul_assign(&mut self, q: &Quaternion) {
-        self.r = self.r * q.r - self.i * q.i - self.j * q.j - self.k * q.k;
-        self.i = self.r * q.i + self.i * q.r + self.j * q.k - self.k * q.j;
-        self.j = self.r * q.j + self.j * q.r + self.k * q.i - self.i * q.k;
-        self.k = self.r * q.k + self.k * q.r + self.i * q.j - self.j * q.i;
+        // all components have to be calculated from the old values
+        *self = &*self * q;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn assert_close(a: &Quaternion, b: &Quaternion) {
+        let difference =
+            (a.r - b.r).abs() + (a.i - b.i).abs() + (a.j - b.j).abs() + (a.k - b.k).abs();
+        assert!(difference < 1.0e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn mul_assign_matches_mul() {
+        let q = Quaternion::new(0.5, -1.0, 2.0, 0.25);
+        let p = Quaternion::new(-0.75, 0.5, 1.5, -2.0);
+        let mut product = q;
+        product *= &p;
+        assert_close(&product, &(&q * &p));
+    }
+
+    #[test]
+    fn angular_velocity_rotates_the_orientation() {
+        // a quarter turn around z in one second
+        let rotation = Vec3::from_values(0.0, 0.0, PI as Real / 2.0);
+        let mut orientation = Quaternion::identity();
+        let steps = 1000;
+        for _ in 0..steps {
+            orientation.add_scaled_vector(&rotation, 1.0 / steps as Real);
+            orientation = orientation.normalize();
+        }
+        let half_angle = PI as Real / 4.0;
+        let expected = Quaternion::new(half_angle.cos(), 0.0, 0.0, half_angle.sin());
+        let error = (orientation.r - expected.r).abs() + (orientation.k - expected.k).abs();
+        assert!(error < 1.0e-3, "{:?}", orientation);
+        assert!(orientation.i.abs() < 1.0e-12 && orientation.j.abs() < 1.0e-12);
     }
 }
@@ -1,11 +1,27 @@
+use crate::types::Real;
 use crate::vector::Vec3;
 use rand::Rng;
+use std::f64::consts::PI;
 
 pub fn random_vector(min: Vec3, max: Vec3) -> Vec3 {
-    let mut rng = rand::thread_rng();
     Vec3::from_values(
-        rng.gen_range(min.x, max.x),
-        rng.gen_range(min.y, max.y),
-        rng.gen_range(min.z, max.z),
+        random_real(min.x, max.x),
+        random_real(min.y, max.y),
+        random_real(min.z, max.z),
     )
 }
+
+pub fn random_real(min: Real, max: Real) -> Real {
+    if min >= max {
+        return min;
+    }
+    rand::thread_rng().gen_range(min, max)
+}
+
+/// Uniformly distributed direction
+pub fn random_unit_vector() -> Vec3 {
+    let z = random_real(-1.0, 1.0);
+    let angle = random_real(0.0, 2.0 * PI as Real);
+    let r = (1.0 - z * z).sqrt();
+    Vec3::from_values(r * angle.cos(), r * angle.sin(), z)
+}
@@ -0,0 +1,275 @@
+use crate::particle::force_generator::buoyancy::{sphere_water_drag, submerged_part};
+use crate::particle::force_generator::drag::drag_force;
+use crate::rigid_body::force_generator::ForceGenerator;
+use crate::rigid_body::RigidBody;
+use crate::types::Real;
+use crate::vector::Vec3;
+use crate::water_surface::{FlatWater, WaterSurface};
+use crate::GRAVITY;
+
+/// A part of the hull sampled against the water surface
+#[derive(Copy, Clone, Debug)]
+pub struct BuoyancyPoint {
+    // in body space
+    pub position: Vec3,
+    // cubic meters of the hull around the point
+    pub volume: Real,
+    // the height of the part, it's centered at the point
+    pub max_depth: Real,
+    // quadratic drag against the water when fully submerged
+    water_k2: Real,
+}
+
+impl BuoyancyPoint {
+    pub fn new(position: Vec3, volume: Real, max_depth: Real) -> Self {
+        BuoyancyPoint {
+            position,
+            volume,
+            max_depth,
+            water_k2: 0.0,
+        }
+    }
+}
+
+/// Buoyancy sampled in several points of a body, each point pushes where it is,
+/// so a hull rights itself and pitches and rolls on waves
+pub struct Buoyancy<W: WaterSurface = FlatWater> {
+    points: Vec<BuoyancyPoint>,
+    // kg per cubic meter
+    liquid_density: Real,
+    water: W,
+    gravity: Real,
+    // drag of every point against the water
+    water_k1: Real,
+    water_k2: Real,
+    // every point gets the quadratic drag of a sphere of its volume instead of water_k2
+    sphere_water_drag: bool,
+}
+
+impl<W: WaterSurface> Buoyancy<W> {
+    /// There is no water drag by default, without it a floating body keeps bobbing
+    pub fn new(liquid_density: Real, water: W) -> Self {
+        Buoyancy {
+            points: Vec::new(),
+            liquid_density,
+            water,
+            gravity: GRAVITY.magnitude(),
+            water_k1: 0.0,
+            water_k2: 0.0,
+            sphere_water_drag: false,
+        }
+    }
+
+    pub fn add_point(&mut self, mut point: BuoyancyPoint) -> &mut Self {
+        point.water_k2 = self.get_point_water_k2(&point);
+        self.points.push(point);
+        self
+    }
+
+    /// Sets the drag of every point against the water, added ones included
+    pub fn set_water_drag(&mut self, k1: Real, k2: Real) -> &mut Self {
+        self.water_k1 = k1;
+        self.water_k2 = k2;
+        self.sphere_water_drag = false;
+        self.update_points_water_drag();
+        self
+    }
+
+    /// Quadratic water drag of a sphere of the point volume for every point
+    pub fn set_sphere_water_drag(&mut self) -> &mut Self {
+        self.water_k1 = 0.0;
+        self.sphere_water_drag = true;
+        self.update_points_water_drag();
+        self
+    }
+
+    fn get_point_water_k2(&self, point: &BuoyancyPoint) -> Real {
+        if self.sphere_water_drag {
+            sphere_water_drag(point.volume, self.liquid_density)
+        } else {
+            self.water_k2
+        }
+    }
+
+    fn update_points_water_drag(&mut self) {
+        for i in 0..self.points.len() {
+            self.points[i].water_k2 = self.get_point_water_k2(&self.points[i]);
+        }
+    }
+
+    pub fn set_gravity(&mut self, gravity: Real) -> &mut Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn get_points(&self) -> &[BuoyancyPoint] {
+        &self.points
+    }
+
+    pub fn get_water(&self) -> &W {
+        &self.water
+    }
+
+    pub fn get_water_mut(&mut self) -> &mut W {
+        &mut self.water
+    }
+
+    /// From 0 above the water to 1 when all points are fully submerged, by volume
+    pub fn get_submerged_part(&self, body: &RigidBody) -> Real {
+        let total_volume: Real = self.points.iter().map(|point| point.volume).sum();
+        if total_volume == 0.0 {
+            return 0.0;
+        }
+        self.points
+            .iter()
+            .map(|point| {
+                let depth = self
+                    .water
+                    .get_depth(&body.get_point_in_world_space(&point.position));
+                point.volume * submerged_part(depth, point.max_depth)
+            })
+            .sum::<Real>()
+            / total_volume
+    }
+}
+
+impl<W: WaterSurface> ForceGenerator for Buoyancy<W> {
+    fn update_force(&mut self, body: &mut RigidBody, _duration: Real) {
+        for point in self.points.iter() {
+            let position = body.get_point_in_world_space(&point.position);
+            let submerged_part = submerged_part(self.water.get_depth(&position), point.max_depth);
+            if submerged_part == 0.0 {
+                continue;
+            }
+            let displaced_weight = self.liquid_density * point.volume * self.gravity;
+            let mut force = &self.water.get_up(&position) * (displaced_weight * submerged_part);
+
+            let relative_velocity =
+                body.get_velocity_at_point(&position) - self.water.get_velocity(&position);
+            let drag = drag_force(&relative_velocity, self.water_k1, point.water_k2);
+            force.add_scaled(&drag, submerged_part);
+            body.add_force_at_point(&force, &position);
+        }
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.water.set_time(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::Matrix3;
+    use crate::quaternion::Quaternion;
+    use crate::water_surface::{SineWaves, Wave};
+
+    // a flat box 2 x 0.5 x 2 meters sampled in the centers of its octants
+    fn flat_box_buoyancy() -> Buoyancy {
+        let mut buoyancy = Buoyancy::new(1000.0, FlatWater::new(0.0));
+        for &x in [-0.5, 0.5].iter() {
+            for &y in [-0.125, 0.125].iter() {
+                for &z in [-0.5, 0.5].iter() {
+                    buoyancy.add_point(BuoyancyPoint::new(Vec3::from_values(x, y, z), 0.25, 0.25));
+                }
+            }
+        }
+        buoyancy
+    }
+
+    // the box floating at its draft, 30% of the water density
+    fn floating_box() -> RigidBody {
+        let mut body = RigidBody::new();
+        body.set_mass(600.0)
+            .set_damping(1.0, 1.0)
+            .set_acceleration(GRAVITY)
+            .set_position(Vec3::from_values(0.0, 0.1, 0.0));
+        body.set_inertia_tensor(&Matrix3::identity() * 130.0);
+        body
+    }
+
+    #[test]
+    fn floating_box_sinks_to_its_draft() {
+        let mut buoyancy = flat_box_buoyancy();
+        buoyancy.set_water_drag(3000.0, 0.0);
+        let mut body = RigidBody::new();
+        // 30% of the water density
+        body.set_mass(600.0)
+            .set_damping(1.0, 1.0)
+            .set_acceleration(GRAVITY)
+            .set_position(Vec3::from_values(0.0, 0.5, 0.0));
+        body.set_inertia_tensor(&Matrix3::identity() * 130.0);
+        for _ in 0..10000 {
+            buoyancy.update_force(&mut body, 0.001);
+            body.integrate(0.001);
+        }
+        // 0.3 of the 0.5 meters height is under the water
+        assert!((body.get_position().y - 0.1).abs() < 1.0e-3);
+        assert!((buoyancy.get_submerged_part(&body) - 0.3).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn there_is_no_water_drag_by_default() {
+        let mut buoyancy = flat_box_buoyancy();
+        let mut body = RigidBody::new();
+        body.set_damping(1.0, 1.0)
+            .set_position(Vec3::from_values(0.0, -2.0, 0.0))
+            .set_velocity(Vec3::from_values(3.0, 0.0, 0.0));
+        buoyancy.update_force(&mut body, 0.01);
+        body.integrate(0.01);
+        assert!((body.get_velocity().x - 3.0).abs() < 1.0e-12);
+
+        buoyancy.set_sphere_water_drag();
+        body.set_velocity(Vec3::from_values(3.0, 0.0, 0.0));
+        buoyancy.update_force(&mut body, 0.01);
+        body.integrate(0.01);
+        assert!(body.get_velocity().x < 2.9);
+    }
+
+    #[test]
+    fn rolled_box_rights_itself() {
+        let mut buoyancy = flat_box_buoyancy();
+        buoyancy.set_water_drag(3000.0, 0.0);
+        let mut body = floating_box();
+        // rolled by 0.4 radians around z
+        body.set_orientation(Quaternion::new(
+            (0.2 as Real).cos(),
+            0.0,
+            0.0,
+            (0.2 as Real).sin(),
+        ));
+
+        buoyancy.update_force(&mut body, 0.001);
+        assert!(body.get_torque_accum().z < 0.0);
+        body.clear_accumulators();
+
+        for _ in 0..10000 {
+            buoyancy.update_force(&mut body, 0.001);
+            body.integrate(0.001);
+        }
+        let up = body.get_direction_in_world_space(&Vec3::from_values(0.0, 1.0, 0.0));
+        assert!(up.y > 0.9999, "{:?}", up);
+    }
+
+    #[test]
+    fn wave_under_one_side_pitches_the_body() {
+        let mut water = SineWaves::new(0.0);
+        // the crest is under the positive x side of the box
+        water.add_wave(Wave::new(Vec3::from_values(1.0, 0.0, 0.0), 0.2, 4.0));
+        let mut buoyancy = Buoyancy::new(1000.0, water);
+        buoyancy.set_water_drag(3000.0, 0.0);
+        for point in flat_box_buoyancy().get_points().iter() {
+            buoyancy.add_point(*point);
+        }
+        let mut body = floating_box();
+
+        for _ in 0..100 {
+            buoyancy.update_force(&mut body, 0.001);
+            body.integrate(0.001);
+        }
+        // the side on the crest is pushed up, the body turns counterclockwise around z
+        assert!(body.get_rotation().z > 0.0);
+        let side = body.get_direction_in_world_space(&Vec3::from_values(1.0, 0.0, 0.0));
+        assert!(side.y > 0.0);
+    }
+}
@@ -0,0 +1,12 @@
+pub mod buoyancy;
+
+use crate::rigid_body::RigidBody;
+use crate::types::Real;
+
+pub trait ForceGenerator {
+    fn update_force(&mut self, body: &mut RigidBody, duration: Real);
+
+    /// Tells time dependent generators the simulation time, should be called
+    /// once a step before forces are updated
+    fn set_time(&mut self, _time: Real) {}
+}
@@ -1,3 +1,5 @@
+pub mod force_generator;
+
 use crate::matrix::{Matrix3, Matrix4};
 use crate::quaternion::Quaternion;
 use crate::types::Real;
@@ -39,6 +41,29 @@ pub struct RigidBody {
 }
 
 impl RigidBody {
+    /// A body of 1 kg with the inertia tensor of a 1 m solid sphere, at rest in the origin
+    pub fn new() -> Self {
+        let mut body = RigidBody {
+            inverse_mass: 1.0,
+            linear_dumping: 0.99,
+            angular_damping: 0.99,
+            position: Vec3::new(),
+            orientation: Quaternion::identity(),
+            velocity: Vec3::new(),
+            acceleration: Vec3::new(),
+            rotation: Vec3::new(),
+            transform_matrix: Matrix4::new(),
+            inverse_inertia_tensor: Matrix3::new(),
+            inverse_inertia_tensor_world: Matrix3::new(),
+            is_awake: true,
+            force_accum: Vec3::new(),
+            torque_accum: Vec3::new(),
+        };
+        body.set_inertia_tensor(&Matrix3::identity() * 0.4);
+        body.calculate_derived_data();
+        body
+    }
+
     pub fn integrate(&mut self, duration: Real) {
         let mut last_frame_acceleration = self.acceleration.clone();
         last_frame_acceleration.add_scaled(&self.force_accum, self.inverse_mass);
@@ -47,6 +72,7 @@ impl RigidBody {
             .inverse_inertia_tensor_world
             .transform(&self.torque_accum);
 
+        self.velocity.add_scaled(&last_frame_acceleration, duration);
         self.rotation.add_scaled(&angular_acceleration, duration);
 
         self.velocity *= self.linear_dumping.powf(duration);
@@ -68,8 +94,8 @@ impl RigidBody {
      */
     pub fn add_force_at_body_point(&mut self, force: &Vec3, point: &Vec3) {
         // Convert to coordinates relative to center of mass.
-        self.get_point_in_world_space(point);
-        self.add_force_at_point(force, point);
+        let point = self.get_point_in_world_space(point);
+        self.add_force_at_point(force, &point);
         self.is_awake = true;
     }
 
@@ -93,6 +119,19 @@ impl RigidBody {
         self.transform_matrix.transform(point)
     }
 
+    pub fn get_point_in_local_space(&self, point: &Vec3) -> Vec3 {
+        self.transform_matrix.transform_inverse(*point)
+    }
+
+    pub fn get_direction_in_world_space(&self, direction: &Vec3) -> Vec3 {
+        self.transform_matrix.transform_direction(*direction)
+    }
+
+    /// Velocity of the body at the point given in world space, rotation included
+    pub fn get_velocity_at_point(&self, point: &Vec3) -> Vec3 {
+        self.velocity + self.rotation % (*point - self.position)
+    }
+
     pub fn clear_accumulators(&mut self) {
         self.force_accum.set_to_zero();
         self.torque_accum.set_to_zero();
@@ -103,10 +142,23 @@ impl RigidBody {
         self.is_awake = true;
     }
 
+    pub fn add_torque(&mut self, torque: &Vec3) {
+        self.torque_accum += *torque;
+        self.is_awake = true;
+    }
+
+    pub fn get_force_accum(&self) -> Vec3 {
+        self.force_accum
+    }
+
+    pub fn get_torque_accum(&self) -> Vec3 {
+        self.torque_accum
+    }
+
     // TODO pure function?
     fn calculate_derived_data(&mut self) {
-        self.orientation.normalize();
-        calculate_transform_matrix(&self.position, &self.orientation);
+        self.orientation = self.orientation.normalize();
+        self.transform_matrix = calculate_transform_matrix(&self.position, &self.orientation);
         // TODO first argument must be inverse_inertia_tensor_world
         transform_inertia_tensor(
             &mut self.inverse_inertia_tensor_world,
@@ -119,6 +171,98 @@ impl RigidBody {
     pub fn set_inertia_tensor(&mut self, mut inertia_tensor: Matrix3) {
         inertia_tensor.invert();
         self.inverse_inertia_tensor = inertia_tensor;
+        self.calculate_derived_data();
+    }
+
+    pub fn set_mass(&mut self, mass: Real) -> &mut Self {
+        if mass <= 0.0 {
+            panic!("Mass should be greater then 0");
+        }
+        self.inverse_mass = 1.0 / mass;
+        self
+    }
+
+    pub fn set_inverse_mass(&mut self, inverse_mass: Real) -> &mut Self {
+        if inverse_mass < 0.0 {
+            panic!("Mass cannot be less or greater then 0");
+        }
+        self.inverse_mass = inverse_mass;
+        self
+    }
+
+    pub fn get_mass(&self) -> Real {
+        if self.is_infinite_mass() {
+            return Real::INFINITY;
+        }
+        1.0 / self.inverse_mass
+    }
+
+    pub fn get_inverse_mass(&self) -> Real {
+        self.inverse_mass
+    }
+
+    pub fn is_infinite_mass(&self) -> bool {
+        self.inverse_mass == 0.0
+    }
+
+    pub fn set_damping(&mut self, linear_damping: Real, angular_damping: Real) -> &mut Self {
+        self.linear_dumping = linear_damping;
+        self.angular_damping = angular_damping;
+        self
+    }
+
+    pub fn set_position(&mut self, position: Vec3) -> &mut Self {
+        self.position = position;
+        self.calculate_derived_data();
+        self
+    }
+
+    pub fn get_position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn set_orientation(&mut self, orientation: Quaternion) -> &mut Self {
+        self.orientation = orientation;
+        self.calculate_derived_data();
+        self
+    }
+
+    pub fn get_orientation(&self) -> Quaternion {
+        self.orientation
+    }
+
+    pub fn set_velocity(&mut self, velocity: Vec3) -> &mut Self {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn get_velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    /// Sets the angular velocity
+    pub fn set_rotation(&mut self, rotation: Vec3) -> &mut Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn get_rotation(&self) -> Vec3 {
+        self.rotation
+    }
+
+    pub fn set_acceleration(&mut self, acceleration: Vec3) -> &mut Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    pub fn get_acceleration(&self) -> Vec3 {
+        self.acceleration
+    }
+}
+
+impl Default for RigidBody {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -191,3 +335,63 @@ fn transform_inertia_tensor(
     iit_world.data[7] = t52 * rotmat.data[4] + t57 * rotmat.data[5] + t62 * rotmat.data[6];
     iit_world.data[8] = t52 * rotmat.data[8] + t57 * rotmat.data[9] + t62 * rotmat.data[10];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body() -> RigidBody {
+        let mut body = RigidBody::new();
+        body.set_damping(1.0, 1.0);
+        body
+    }
+
+    #[test]
+    fn linear_velocity_moves_the_body() {
+        let mut body = body();
+        body.set_velocity(Vec3::from_values(2.0, 0.0, -1.0));
+        for _ in 0..10 {
+            body.integrate(0.1);
+        }
+        assert!((body.get_position() - Vec3::from_values(2.0, 0.0, -1.0)).magnitude() < 1.0e-12);
+        // the transform follows the body
+        let origin = body.get_point_in_world_space(&Vec3::new());
+        assert!((origin - body.get_position()).magnitude() < 1.0e-12);
+    }
+
+    #[test]
+    fn force_accelerates_the_body() {
+        let mut body = body();
+        body.set_mass(2.0);
+        body.add_force(&Vec3::from_values(0.0, 4.0, 0.0));
+        body.integrate(0.5);
+        assert!((body.get_velocity() - Vec3::from_values(0.0, 1.0, 0.0)).magnitude() < 1.0e-12);
+    }
+
+    #[test]
+    fn orientation_stays_normalized() {
+        let mut body = body();
+        body.set_rotation(Vec3::from_values(3.0, -2.0, 5.0));
+        for _ in 0..1000 {
+            body.integrate(0.01);
+            let q = body.get_orientation();
+            let magnitude = (q.r * q.r + q.i * q.i + q.j * q.j + q.k * q.k).sqrt();
+            assert!((magnitude - 1.0).abs() < 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn force_at_body_point_is_applied_in_world_space() {
+        let mut body = body();
+        body.set_position(Vec3::from_values(10.0, 0.0, 0.0));
+        body.add_force_at_body_point(
+            &Vec3::from_values(0.0, 1.0, 0.0),
+            &Vec3::from_values(1.0, 0.0, 0.0),
+        );
+        body.integrate(1.0);
+        // the torque of a force one meter from the center of mass, not at the
+        // world point (1, 0, 0), spins the body with the inverse inertia of 2.5
+        let rotation = body.get_rotation();
+        assert!((rotation - Vec3::from_values(0.0, 0.0, 2.5)).magnitude() < 1.0e-12);
+    }
+}
@@ -0,0 +1,374 @@
+use crate::plane::Plane;
+use crate::types::Real;
+use crate::vector::Vec3;
+use std::f64::consts::PI;
+
+const GRAVITY_ACCELERATION: Real = 9.81;
+// newton iterations inverting the horizontal displacement of gerstner waves
+const GERSTNER_ITERATIONS: u32 = 6;
+
+/// Surface of a body of water sampled by buoyancy generators
+pub trait WaterSurface {
+    /// How deep the point is under the surface, negative above it
+    fn get_depth(&self, position: &Vec3) -> Real;
+
+    /// The direction out of the water, buoyancy pushes along it
+    fn get_up(&self, _position: &Vec3) -> Vec3 {
+        Vec3::from_values(0.0, 1.0, 0.0)
+    }
+
+    /// Velocity of the water at the point, currents and the motion of waves
+    fn get_velocity(&self, _position: &Vec3) -> Vec3 {
+        Vec3::new()
+    }
+
+    /// Animated surfaces change with the simulation time
+    fn set_time(&mut self, _time: Real) {}
+}
+
+/// Still water with a flat surface of any orientation
+pub struct FlatWater {
+    // the water is on the negative side
+    surface: Plane,
+    current: Vec3,
+}
+
+impl FlatWater {
+    /// Horizontal surface at the height
+    pub fn new(height: Real) -> Self {
+        Self::from_plane(Plane::new(Vec3::from_values(0.0, 1.0, 0.0), height))
+    }
+
+    pub fn from_plane(surface: Plane) -> Self {
+        Self {
+            surface,
+            current: Vec3::new(),
+        }
+    }
+
+    pub fn set_current(&mut self, current: Vec3) -> &mut Self {
+        self.current = current;
+        self
+    }
+}
+
+impl WaterSurface for FlatWater {
+    fn get_depth(&self, position: &Vec3) -> Real {
+        -self.surface.distance(position)
+    }
+
+    fn get_up(&self, _position: &Vec3) -> Vec3 {
+        self.surface.normal
+    }
+
+    fn get_velocity(&self, _position: &Vec3) -> Vec3 {
+        self.current
+    }
+}
+
+/// A single travelling wave of a wavy surface
+#[derive(Copy, Clone, Debug)]
+pub struct Wave {
+    // horizontal, in the XZ plane
+    direction: Vec3,
+    amplitude: Real,
+    wave_number: Real,
+    // angular frequency
+    frequency: Real,
+    phase: Real,
+}
+
+impl Wave {
+    /// The speed is the one of waves on deep water, longer waves are faster
+    pub fn new(direction: Vec3, amplitude: Real, wavelength: Real) -> Self {
+        let mut direction = Vec3::from_values(direction.x, 0.0, direction.z);
+        direction.normalize();
+        let wave_number = 2.0 * PI as Real / wavelength;
+        Self {
+            direction,
+            amplitude,
+            wave_number,
+            frequency: (GRAVITY_ACCELERATION * wave_number).sqrt(),
+            phase: 0.0,
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: Real) -> &mut Self {
+        self.frequency = speed * self.wave_number;
+        self
+    }
+
+    pub fn set_phase(&mut self, phase: Real) -> &mut Self {
+        self.phase = phase;
+        self
+    }
+
+    fn get_angle(&self, position: &Vec3, time: Real) -> Real {
+        self.wave_number * (&self.direction * position) - self.frequency * time + self.phase
+    }
+
+    // motion of water in waves fades exponentially with depth
+    fn get_attenuation(&self, depth: Real) -> Real {
+        (-self.wave_number * depth.max(0.0)).exp()
+    }
+}
+
+/// Waves as a sum of sines over a horizontal plane, smooth and rounded
+pub struct SineWaves {
+    height: Real,
+    waves: Vec<Wave>,
+    current: Vec3,
+    time: Real,
+}
+
+impl SineWaves {
+    pub fn new(height: Real) -> Self {
+        Self {
+            height,
+            waves: Vec::new(),
+            current: Vec3::new(),
+            time: 0.0,
+        }
+    }
+
+    pub fn add_wave(&mut self, wave: Wave) -> &mut Self {
+        self.waves.push(wave);
+        self
+    }
+
+    pub fn set_current(&mut self, current: Vec3) -> &mut Self {
+        self.current = current;
+        self
+    }
+
+    pub fn get_height(&self, position: &Vec3) -> Real {
+        self.height
+            + self
+                .waves
+                .iter()
+                .map(|wave| wave.amplitude * wave.get_angle(position, self.time).sin())
+                .sum::<Real>()
+    }
+}
+
+impl WaterSurface for SineWaves {
+    fn get_depth(&self, position: &Vec3) -> Real {
+        self.get_height(position) - position.y
+    }
+
+    fn get_velocity(&self, position: &Vec3) -> Vec3 {
+        let depth = self.height - position.y;
+        let mut velocity = self.current;
+        for wave in self.waves.iter() {
+            let (sin, cos) = wave.get_angle(position, self.time).sin_cos();
+            let speed = wave.amplitude * wave.frequency * wave.get_attenuation(depth);
+            velocity.add_scaled(&wave.direction, speed * sin);
+            velocity.y -= speed * cos;
+        }
+        velocity
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.time = time;
+    }
+}
+
+/// Trochoidal waves, water moves in circles and gathers in sharp crests.
+/// See Tessendorf, "Simulating Ocean Water"
+pub struct GerstnerWaves {
+    height: Real,
+    waves: Vec<Wave>,
+    // from 0 (sine waves) to 1 (the sharpest crests without loops)
+    steepness: Real,
+    current: Vec3,
+    time: Real,
+}
+
+impl GerstnerWaves {
+    pub fn new(height: Real, steepness: Real) -> Self {
+        Self {
+            height,
+            waves: Vec::new(),
+            steepness: steepness.clamp(0.0, 1.0),
+            current: Vec3::new(),
+            time: 0.0,
+        }
+    }
+
+    pub fn add_wave(&mut self, wave: Wave) -> &mut Self {
+        self.waves.push(wave);
+        self
+    }
+
+    pub fn set_current(&mut self, current: Vec3) -> &mut Self {
+        self.current = current;
+        self
+    }
+
+    pub fn get_height(&self, position: &Vec3) -> Real {
+        let rest = self.find_rest_position(position);
+        self.height
+            + self
+                .waves
+                .iter()
+                .map(|wave| wave.amplitude * wave.get_angle(&rest, self.time).sin())
+                .sum::<Real>()
+    }
+
+    // the horizontal crest sharpness of each wave, the sum of all of them is limited
+    fn get_sharpness(&self, wave: &Wave) -> Real {
+        if wave.amplitude == 0.0 {
+            return 0.0;
+        }
+        self.steepness / (wave.wave_number * wave.amplitude * self.waves.len() as Real)
+    }
+
+    /// Water particles move in circles around their rest positions, finds the one which is
+    /// above the position now. Newton's method, the horizontal displacement is inverted
+    fn find_rest_position(&self, position: &Vec3) -> Vec3 {
+        let mut rest = Vec3::from_values(position.x, 0.0, position.z);
+        for _ in 0..GERSTNER_ITERATIONS {
+            let mut residual = Vec3::from_values(rest.x - position.x, 0.0, rest.z - position.z);
+            // symmetric jacobian of the displaced position by the rest one
+            let (mut xx, mut xz, mut zz) = (1.0, 0.0, 1.0);
+            for wave in self.waves.iter() {
+                let (sin, cos) = wave.get_angle(&rest, self.time).sin_cos();
+                let sharpness = self.get_sharpness(wave) * wave.amplitude;
+                residual.add_scaled(&wave.direction, sharpness * cos);
+                let slope = -sharpness * wave.wave_number * sin;
+                xx += slope * wave.direction.x * wave.direction.x;
+                xz += slope * wave.direction.x * wave.direction.z;
+                zz += slope * wave.direction.z * wave.direction.z;
+            }
+            // only the sharpest crests with the steepness of 1 give zero
+            let determinant = xx * zz - xz * xz;
+            if determinant.abs() <= Real::EPSILON {
+                break;
+            }
+            rest.x -= (zz * residual.x - xz * residual.z) / determinant;
+            rest.z -= (xx * residual.z - xz * residual.x) / determinant;
+        }
+        rest
+    }
+}
+
+impl WaterSurface for GerstnerWaves {
+    fn get_depth(&self, position: &Vec3) -> Real {
+        self.get_height(position) - position.y
+    }
+
+    fn get_velocity(&self, position: &Vec3) -> Vec3 {
+        let depth = self.height - position.y;
+        let rest = self.find_rest_position(position);
+        let mut velocity = self.current;
+        for wave in self.waves.iter() {
+            let (sin, cos) = wave.get_angle(&rest, self.time).sin_cos();
+            let speed = wave.amplitude * wave.frequency * wave.get_attenuation(depth);
+            velocity.add_scaled(&wave.direction, self.get_sharpness(wave) * speed * sin);
+            velocity.y -= speed * cos;
+        }
+        velocity
+    }
+
+    fn set_time(&mut self, time: Real) {
+        self.time = time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Real, expected: Real) {
+        assert!(
+            (actual - expected).abs() < 1.0e-9,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    fn wave() -> Wave {
+        Wave::new(Vec3::from_values(1.0, 0.5, 0.0), 0.5, 10.0)
+    }
+
+    #[test]
+    fn tilted_flat_water() {
+        let mut normal = Vec3::from_values(1.0, 1.0, 0.0);
+        normal.normalize();
+        let water = FlatWater::from_plane(Plane::new(normal, 2.0));
+        let position = Vec3::from_values(3.0, -1.0, 5.0);
+        assert_close(water.get_depth(&position), 2.0 - 2.0 / (2.0 as Real).sqrt());
+        assert_close(water.get_depth(&(&normal * 2.0)), 0.0);
+        assert_close((water.get_up(&position) - normal).magnitude(), 0.0);
+    }
+
+    #[test]
+    fn sine_wave_travels_with_the_deep_water_speed() {
+        let mut waves = SineWaves::new(1.0);
+        waves.add_wave(wave());
+        let k = 2.0 * PI as Real / 10.0;
+        let frequency = (GRAVITY_ACCELERATION * k).sqrt();
+        for &(x, time) in [(0.0, 0.0), (2.0, 0.0), (7.5, 1.3), (-3.0, 4.0 as Real)].iter() {
+            waves.set_time(time);
+            let position = Vec3::from_values(x, 0.0, 11.0);
+            // the direction is made horizontal
+            let angle = k * x - frequency * time;
+            assert_close(waves.get_height(&position), 1.0 + 0.5 * angle.sin());
+            assert_close(waves.get_depth(&position), 1.0 + 0.5 * angle.sin());
+
+            // the slope of the surface, its normal is (-slope, 1, 0)
+            let h = 1.0e-5;
+            let slope = (waves.get_height(&Vec3::from_values(x + h, 0.0, 0.0))
+                - waves.get_height(&Vec3::from_values(x - h, 0.0, 0.0)))
+                / (2.0 * h);
+            assert!((slope - 0.5 * k * angle.cos()).abs() < 1.0e-6);
+
+            // water at the surface moves in circles as fast as the surface rises
+            let velocity = waves.get_velocity(&Vec3::from_values(x, 1.0, 0.0));
+            assert_close(velocity.x, 0.5 * frequency * angle.sin());
+            assert_close(velocity.y, -0.5 * frequency * angle.cos());
+        }
+    }
+
+    #[test]
+    fn gerstner_height_is_the_height_of_the_displaced_water() {
+        let mut second = Wave::new(Vec3::from_values(0.3, 0.0, 1.0), 0.2, 4.0);
+        second.set_phase(0.7);
+        let mut waves = GerstnerWaves::new(-1.0, 0.8);
+        waves.add_wave(wave()).add_wave(second);
+
+        for &time in [0.0, 0.6, 3.1 as Real].iter() {
+            waves.set_time(time);
+            for i in 0..20 {
+                let rest = Vec3::from_values(i as Real * 0.7 - 5.0, 0.0, i as Real * 0.3);
+                // the water from the rest position moves along the wave directions to the crests
+                let mut position = rest;
+                let mut height = -1.0;
+                for wave in waves.waves.iter() {
+                    let angle = wave.get_angle(&rest, time);
+                    let sharpness = waves.get_sharpness(wave) * wave.amplitude;
+                    position.add_scaled(&wave.direction, sharpness * angle.cos());
+                    height += wave.amplitude * angle.sin();
+                }
+                assert!((waves.get_height(&position) - height).abs() < 1.0e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn gerstner_crests_are_sharper_than_sine_crests() {
+        let mut gerstner = GerstnerWaves::new(0.0, 0.9);
+        gerstner.add_wave(wave());
+        let mut sine = SineWaves::new(0.0);
+        sine.add_wave(wave());
+
+        // at time 0 the sine crest is at a quarter of the wavelength
+        let crest = Vec3::from_values(2.5, 0.0, 0.0);
+        assert_close(sine.get_height(&crest), 0.5);
+        let near_crest = Vec3::from_values(3.5, 0.0, 0.0);
+        assert!(gerstner.get_height(&near_crest) < sine.get_height(&near_crest));
+        let trough = Vec3::from_values(7.5, 0.0, 0.0);
+        assert!((gerstner.get_height(&trough) + 0.5).abs() < 1.0e-9);
+    }
+}